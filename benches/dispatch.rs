@@ -0,0 +1,39 @@
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use regex::Regex;
+
+use commnode::{Backpressure, Event, Interest, Subscription, SubscriptionId, index::TopicIndex};
+
+// Builds `n` interests, split among exact regexes, hierarchical wildcards and true regexes.
+fn interests(n: usize) -> Vec<Interest> {
+    (0..n).map(|i| match i % 4 {
+        0 | 1 => Interest::new(Regex::new(&format!("^node{}/model$", i)).unwrap()),
+        2 => Interest::wildcard(&format!("node{}/+/#", i)),
+        _ => Interest::new(Regex::new(&format!("^node{}/(loss|accuracy)$", i)).unwrap()),
+    }).collect()
+}
+
+fn matching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("matching");
+    for n in [16, 128, 1024] {
+        let interests = interests(n);
+        let event = Event::new(&format!("node{}/model", n / 2), Bytes::new());
+
+        group.bench_with_input(BenchmarkId::new("linear", n), &event, |b, event| {
+            b.iter(|| interests.iter().filter(|interest| interest.is_valid(black_box(event))).count())
+        });
+
+        let mut index = TopicIndex::new();
+        for interest in &interests {
+            let (sub, _) = Subscription::new(interest.clone(), 1, Backpressure::DropNewest);
+            index.insert(sub.id(), interest);
+        }
+        group.bench_with_input(BenchmarkId::new("indexed", n), &event, |b, event| {
+            b.iter(|| -> Vec<SubscriptionId> { index.matches(black_box(&event.topic)) })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, matching);
+criterion_main!(benches);
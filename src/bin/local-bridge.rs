@@ -1,17 +1,18 @@
 use commnode::{*, config::*, framing::*};
 use futures::{StreamExt, SinkExt, stream::SplitSink};
 use regex::Regex;
-use tokio::{self, sync::{mpsc::Sender, Mutex}, select, signal, net::{TcpListener, TcpStream}};
+use tokio::{self, sync::{mpsc::{Sender, UnboundedReceiver}, Mutex}, select, signal, net::{TcpListener, TcpStream}};
 use tokio_util::sync::CancellationToken;
 use std::{env, fmt, sync::Arc};
 use serde::{Serialize, Deserialize};
-use toml;
 use bytes::Bytes;
 
 #[tokio::main]
 async fn main() {
+    commnode::logging::init(tracing::Level::DEBUG);
+
     log(Color::Text, "bridge configuration... ");
-    let result = read_toml(env::args_os().skip(1).next().unwrap_or("./config.toml".into()));
+    let result = read_toml(env::args_os().nth(1).unwrap_or("./config.toml".into()));
     let config: BridgeConfig = if let Some(config) = log_unwrap(result) { config } else { return; };
     logln(Color::Ok, "ok");
 
@@ -22,14 +23,16 @@ async fn main() {
     logln(Color::Ok, "ok");
 
     log(Color::Text, "commnode configuration... ");
-    let result = commnode::config::init_connections(&config.configs_path, true, dispatcher.clone(), config.channels_size, token.clone()).await;
-    if let None = log_unwrap(result) { return; }
+    let (status_tx, status_rx) = tokio::sync::mpsc::unbounded_channel();
+    let result = commnode::config::init_connections(&config.configs_path, true, dispatcher.clone(), config.channels_size, status_tx, Default::default(), token.clone()).await;
+    if log_unwrap(result).is_none() { return; }
+    tokio::spawn(log_status(status_rx));
     logln(Color::Ok, "ok");
 
     log(Color::Text, "local bridge initialization... ");
     init_bridges(config, dispatcher.clone(), token.clone());
     logln(Color::Ok, "ok");
-    println!("");
+    println!();
 
     select! {
         _ = token.cancelled() => logln(Color::Err, "program crashed!"),
@@ -44,14 +47,19 @@ fn init_bridges(config: BridgeConfig, dispatcher: Sender<Command>, token: Cancel
     }
 }
 
-async fn init_bridge(socket: String, dispatcher: Sender<Command>, token: CancellationToken) {
-    if let Ok(listener) = TcpListener::bind(socket).await {
+async fn init_bridge(socket: BridgeSocket, dispatcher: Sender<Command>, token: CancellationToken) {
+    let (address, max_frame_length) = match socket {
+        BridgeSocket::Address(address) => (address, None),
+        BridgeSocket::Config { address, max_frame_length } => (address, max_frame_length),
+    };
+    let max_frame_length = max_frame_length.unwrap_or(DEFAULT_MAX_FRAME_LENGTH);
+    if let Ok(listener) = TcpListener::bind(address).await {
         loop {
             select! {
                 _ = token.cancelled() => break,
                 result = listener.accept() => {
                     if let Ok((stream, _)) = result {
-                        let stream = frame_string(stream);
+                        let stream = frame_string_with(stream, max_frame_length);
                         tokio::spawn(handle_connection(stream, dispatcher.clone(), token.child_token()));
                     }
                 }
@@ -60,6 +68,14 @@ async fn init_bridge(socket: String, dispatcher: Sender<Command>, token: Cancell
     }
 }
 
+async fn log_status(mut rx: UnboundedReceiver<Status>) {
+    while let Some(status) = rx.recv().await {
+        if let Status::Failed(protocol, address, e) = status {
+            logln(Color::Err, &format!("{} channel {} failed: {}", protocol, address, e));
+        }
+    }
+}
+
 enum ReqOutcome {
     Disconnected,
     Crashed,
@@ -76,6 +92,9 @@ async fn handle_connection(stream: FramedString<TcpStream>, dispatcher: Sender<C
                 let mut req_outcome = ReqOutcome::Disconnected;
                 if let Some(result) = option {
                     req_outcome = ReqOutcome::Crashed;
+                    if let Err(e) = &result {
+                        logln(Color::Err, &format!("request rejected: {}", e));
+                    }
                     if let Ok(bytes) = result {
                         if let Ok(str_ref) = std::str::from_utf8(&bytes) {
                             if let Ok(request) = toml::from_str::<Request>(str_ref) {
@@ -135,7 +154,7 @@ async fn handle_request(tx: Arc<Mutex<SplitSink<FramedString<TcpStream>, Bytes>>
             if let Some(sends) = request.sends {
                 for send in &sends {
                     if let Some(expect) = &send.expect {
-                        let rx = Subscription::subscribe(Interest::new(Regex::new(&expect.recv.interest).unwrap()), expect.recv.num.into(), dispatcher.clone()).await.unwrap();
+                        let (_, rx) = Subscription::subscribe(Interest::new(Regex::new(&expect.recv.interest).unwrap()), expect.recv.num.into(), Backpressure::DropNewest, dispatcher.clone()).await.unwrap();
                         handles.push(tokio::spawn(launch_n_recvs(expect.recv.clone(), rx)));
                     }
                 }
@@ -163,11 +182,11 @@ async fn handle_request(tx: Arc<Mutex<SplitSink<FramedString<TcpStream>, Bytes>>
 
 async fn launch_recv(tx: Arc<Mutex<SplitSink<FramedString<TcpStream>, Bytes>>>, recv: Recv, dispatcher: Sender<Command>, token: CancellationToken) {
     select! {
-        _ = token.cancelled() => {()},
+        _ = token.cancelled() => {},
         _ = async move {
                     if recv.num == 0 {
                         tokio::spawn(async move {
-                            let mut rx = Subscription::subscribe(Interest::new(Regex::new(&recv.interest).unwrap()), 32, dispatcher.clone()).await.unwrap();
+                            let (_, mut rx) = Subscription::subscribe(Interest::new(Regex::new(&recv.interest).unwrap()), 32, Backpressure::DropNewest, dispatcher.clone()).await.unwrap();
                             while let Some(arc) = rx.recv().await {
                                 let res = Res {
                                     id: recv.id.clone(),
@@ -182,7 +201,7 @@ async fn launch_recv(tx: Arc<Mutex<SplitSink<FramedString<TcpStream>, Bytes>>>,
                         });
                     } else {
                         tokio::spawn(async move {
-                            let mut rx = Subscription::subscribe(Interest::new(Regex::new(&recv.interest).unwrap()), recv.num.into(), dispatcher.clone()).await.unwrap();
+                            let (_, mut rx) = Subscription::subscribe(Interest::new(Regex::new(&recv.interest).unwrap()), recv.num.into(), Backpressure::DropNewest, dispatcher.clone()).await.unwrap();
                             for _ in 0..recv.num {
                                 if let Some(arc) = rx.recv().await {
                                     let res = Res {
@@ -200,11 +219,11 @@ async fn launch_recv(tx: Arc<Mutex<SplitSink<FramedString<TcpStream>, Bytes>>>,
                             }
                         });
                     }
-                } => {()}
+                } => {}
     }
 }
 
-async fn launch_n_recvs(recv: Recv, mut rx: tokio::sync::mpsc::Receiver<Arc<Event>>) -> Option<Res> {
+async fn launch_n_recvs(recv: Recv, mut rx: Inbox) -> Option<Res> {
     let mut packets = Vec::with_capacity(recv.num.into());
     if recv.num == 0 {
         return None
@@ -225,7 +244,19 @@ struct BridgeConfig {
     pub channels_size: usize,
     
     pub configs_path: String,
-    pub sockets: Vec<String>,   
+    pub sockets: Vec<BridgeSocket>,
+}
+
+/// Socket of the bridge, either its address, or its address and the maximum length in bytes of the requests,
+/// e.g. `sockets = ["127.0.0.1:9000", { address = "127.0.0.1:9001", max_frame_length = 65536 }]`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum BridgeSocket {
+    Address(String),
+    Config {
+        address: String,
+        max_frame_length: Option<usize>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
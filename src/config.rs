@@ -1,13 +1,16 @@
-use std::{fs, path::Path, error::Error, sync::Arc};
+use std::{collections::HashMap, fs, io, path::Path, sync::Arc, time::Duration};
 
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use regex::Regex;
-use tokio::{select, sync::mpsc};
+use tokio::{select, sync::{Mutex, mpsc, watch}};
 use tokio_util::sync::CancellationToken;
 use toml;
 use serde::{Serialize, Deserialize, de::DeserializeOwned};
 
-use crate::{protocols::{Protocol, tcp, udp}, Interest, Subscription, Command, Event};
+use crate::{framing::{Compression, DEFAULT_MAX_FRAME_LENGTH, Format, FramingConfig}, protocols::{Protocol, heartbeat::{connection_lost_event, is_system}, quic, tcp::{self, Backoff, ConnectionState}, tls::{self, TlsConfig}, transport::{self, Registry}, udp, ws::{self, WsMode}}, Interest, Subscription, SubscriptionHandle, Backpressure, Command, Error, Event, error::Result};
+#[cfg(unix)]
+use crate::protocols::unix;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
@@ -20,11 +23,130 @@ pub struct Node {
     pub channels: Vec<Channel>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Channel {
     pub address: String,
     pub protocol: Protocol,
-    pub interest: String,
+    pub interest: InterestSpec,
+    /// TLS settings, used only by the `TLS` and `QUIC` protocols.
+    pub tls: Option<TlsConfig>,
+    /// Encoding of the sent `Event`s, used only by the `WS` protocol.
+    pub ws_mode: Option<WsMode>,
+    /// Interest of the `Event`s flowing back over the same connection, used only by the `TCP` protocol.
+    /// A sender advertises it to the listener, which pushes back the `Event`s matching both it and its own.
+    pub duplex: Option<InterestSpec>,
+    /// Whether the peers exchange the interests of their local `Subscription`s, used only by the `TCP` protocol.
+    /// If set, the `interest` of the channel bounds the `Event`s exchanged in both directions.
+    #[serde(default)]
+    pub propagate: bool,
+    /// Time in milliseconds without receiving anything from a peer after which it is considered lost, used only by the
+    /// `TCP`, `TLS`, `UNIX` and `UDP` receivers. The senders transmit heartbeats while idle, so it should be a few seconds.
+    pub idle_timeout_ms: Option<u64>,
+    /// Compression of the frames sent to the peers accepting it, used only by the `TCP`, `TLS` and `UNIX` senders, by the
+    /// linked `TCP` listeners and by the custom protocols with framed `Connection`s.
+    pub compression: Option<Compression>,
+    /// Serialization of the `Event`s, bincode by default, used only by the `TCP`, `TLS` and `UNIX` protocols, and by
+    /// the custom protocols with framed `Connection`s.
+    /// Both the sides of the connection must use the same, as for the channels sharing a connection.
+    pub format: Option<Format>,
+    /// Maximum length in bytes of the frames, 64 MiB by default, used only by the `TCP`, `TLS` and `UNIX` protocols,
+    /// and by the custom protocols with framed `Connection`s.
+    /// A connection receiving a longer frame is closed, and a longer `Event` is dropped by the sender.
+    pub max_frame_length: Option<usize>,
+}
+
+impl Channel {
+    /// Returns the framing of the streams of the channel.
+    pub fn framing(&self) -> FramingConfig {
+        FramingConfig {
+            format: self.format.unwrap_or_default(),
+            compression: self.compression,
+            max_frame_length: self.max_frame_length.unwrap_or(DEFAULT_MAX_FRAME_LENGTH),
+        }
+    }
+}
+
+/// Configuration of an `Interest`, either a regex pattern matched against the topic, or an `InterestExpr`.
+/// 
+/// e.g. `interest = { all = ["^model$", { size = { max = 4096 } }, { not = { source = "^10\\." } }] }`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InterestSpec {
+    Regex(String),
+    Expr(InterestExpr),
+}
+
+/// Configuration of the composite `Interest`s, mirroring its variants.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterestExpr {
+    Exact(String),
+    Wildcard(String),
+    Regex(String),
+    Header {
+        name: String,
+        matches: String,
+    },
+    Size {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    Time {
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    },
+    Source(String),
+    Correlation(u64),
+    All(Vec<InterestSpec>),
+    Any(Vec<InterestSpec>),
+    Not(Box<InterestSpec>),
+}
+
+impl InterestSpec {
+    /// Builds the configured `Interest`, failing if any of its regex patterns is invalid.
+    pub fn build(&self) -> Result<Interest> {
+        let expr = match self {
+            Self::Regex(pattern) => return Ok(Interest::new(Regex::new(pattern)?)),
+            Self::Expr(expr) => expr,
+        };
+        Ok(match expr {
+            InterestExpr::Exact(topic) => Interest::exact(topic),
+            InterestExpr::Wildcard(pattern) => Interest::wildcard(pattern),
+            InterestExpr::Regex(pattern) => Interest::new(Regex::new(pattern)?),
+            InterestExpr::Header { name, matches } => Interest::Header(name.clone(), Regex::new(matches)?),
+            InterestExpr::Size { min, max } => Interest::Size { min: *min, max: *max },
+            InterestExpr::Time { after, before } => Interest::Time { after: *after, before: *before },
+            InterestExpr::Source(pattern) => Interest::Source(Regex::new(pattern)?),
+            InterestExpr::Correlation(id) => Interest::Correlation(*id),
+            InterestExpr::All(specs) => Interest::All(specs.iter().map(Self::build).collect::<Result<_>>()?),
+            InterestExpr::Any(specs) => Interest::Any(specs.iter().map(Self::build).collect::<Result<_>>()?),
+            InterestExpr::Not(spec) => !spec.build()?,
+        })
+    }
+}
+
+impl From<&Interest> for InterestSpec {
+    fn from(interest: &Interest) -> Self {
+        Self::Expr(match interest {
+            Interest::Exact(topic) => InterestExpr::Exact(topic.clone()),
+            Interest::Wildcard(pattern) => InterestExpr::Wildcard(pattern.clone()),
+            Interest::Regex(regex) => return Self::Regex(regex.as_str().to_string()),
+            Interest::Header(name, regex) => InterestExpr::Header { name: name.clone(), matches: regex.as_str().to_string() },
+            Interest::Size { min, max } => InterestExpr::Size { min: *min, max: *max },
+            Interest::Time { after, before } => InterestExpr::Time { after: *after, before: *before },
+            Interest::Source(regex) => InterestExpr::Source(regex.as_str().to_string()),
+            Interest::Correlation(id) => InterestExpr::Correlation(*id),
+            Interest::All(interests) => InterestExpr::All(interests.iter().map(Self::from).collect()),
+            Interest::Any(interests) => InterestExpr::Any(interests.iter().map(Self::from).collect()),
+            Interest::Not(interest) => InterestExpr::Not(Box::new(Self::from(interest.as_ref()))),
+        })
+    }
+}
+
+impl From<&str> for InterestSpec {
+    fn from(pattern: &str) -> Self {
+        Self::Regex(String::from(pattern))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,7 +156,7 @@ pub struct Receiver {
     pub node: Node,
 }
 
-pub fn read_n_toml<T: DeserializeOwned>(path: &str) -> Result<Vec<T>, Box<dyn Error>> {
+pub fn read_n_toml<T: DeserializeOwned>(path: &str) -> Result<Vec<T>> {
     let mut tomls: Vec<T> = Vec::new();
     if Path::new(path).is_dir() {
         let paths = fs::read_dir(path)?;
@@ -43,79 +165,131 @@ pub fn read_n_toml<T: DeserializeOwned>(path: &str) -> Result<Vec<T>, Box<dyn Er
                 tomls.push(parsed);
             }
         }
-    } else {
-        if let Ok(parsed) = read_toml(path) {
-            tomls.push(parsed);
-        }
+    } else if let Ok(parsed) = read_toml(path) {
+        tomls.push(parsed);
     }
     Ok(tomls)
 }
 
-pub fn read_toml<P: AsRef<Path>, T: DeserializeOwned>(path: P) -> Result<T, Box<dyn Error>> {
+pub fn read_toml<P: AsRef<Path>, T: DeserializeOwned>(path: P) -> Result<T> {
     let toml_str: String = fs::read_to_string(path)?;
     let toml_struct: T = toml::from_str(&toml_str)?;
     Ok(toml_struct)
 }
 
-//TODO: implement logs
-pub async fn init_connections(path: &str, adv: bool, dispatcher: mpsc::Sender<Command>, buffer: usize, token: CancellationToken) -> Result<(), Box<dyn Error>> {
+/// Status reports of the connection tasks launched by `init_connections()`.
+#[derive(Debug)]
+pub enum Status {
+    /// A receiver is listening on the address.
+    Listening(Protocol, String),
+    /// A sender is connected to the address.
+    Connected(Protocol, String),
+    /// A sender lost the connection to the address, or failed to establish it, and is going to retry.
+    Disconnected(Protocol, String),
+    /// The task of the receiver or of the sender of the address failed, and has been terminated.
+    Failed(Protocol, String, Error),
+}
+
+// Advertisement of a peer, along with the protocol and the address of the channel receiving it.
+type Advertisement = (Protocol, String, Event);
+
+// Outbound connections shared by all the channels with the same protocol and address.
+type Pool = Arc<Mutex<HashMap<(Protocol, String), Peer>>>;
+
+// Outbound connection to a peer, forwarding the events matching the interest of any of its channels.
+// The handle is missing while the connection is being established, during which the channels just add their interests.
+struct Peer {
+    handle: Option<SubscriptionHandle>,
+    interests: Vec<Interest>,
+    settings: Settings,
+}
+
+// Settings of a connection, which all the channels sharing it must have.
+#[derive(PartialEq)]
+struct Settings {
+    duplex: Option<InterestSpec>,
+    framing: FramingConfig,
+    tls: Option<TlsConfig>,
+    ws_mode: Option<WsMode>,
+}
+
+impl Settings {
+    fn new(channel: &Channel) -> Self {
+        Self {
+            duplex: channel.duplex.clone(),
+            framing: channel.framing(),
+            tls: channel.tls.clone(),
+            ws_mode: channel.ws_mode,
+        }
+    }
+}
+
+pub async fn init_connections(path: &str, adv: bool, dispatcher: mpsc::Sender<Command>, buffer: usize, status: mpsc::UnboundedSender<Status>, registry: Arc<Registry>, token: CancellationToken) -> Result<()> {
     let configs = read_n_toml::<Config>(path)?;
+    let pool = Pool::default();
     for config in configs {
-        let receiver = if let Some(receiver) = config.receiver {
-            Some(Arc::new(receiver))
-        } else {
-            None
-        };
+        let receiver = config.receiver.map(Arc::new);
         if let Some(recv) = receiver.clone() {
             let redirect = if adv {
-                if let Ok(re) = Regex::new(&recv.adv_interest) {
-                    let (tx, rx) = mpsc::channel(buffer);
-                    launch_redirect(rx, dispatcher.clone(), buffer, token.clone());
-                    Some((Interest::new(re), tx))
-                } else {
-                    None
-                }
+                let re = Regex::new(&recv.adv_interest)?;
+                let (tx, rx) = mpsc::channel(buffer);
+                launch_redirect(rx, pool.clone(), dispatcher.clone(), buffer, status.clone(), registry.clone(), token.clone());
+                Some((Interest::new(re), tx))
             } else {
                 None
             };
             for channel in &recv.node.channels {
-                let regex = match Regex::new(&channel.interest) {
-                    Ok(re) => re,
-                    Err(_) => continue,
+                let interest = match channel.interest.build() {
+                    Ok(interest) => interest,
+                    Err(e) => {
+                        let _ = status.send(Status::Failed(channel.protocol.clone(), channel.address.clone(), e));
+                        continue;
+                    },
                 };
-                let interest = Interest::new(regex);
-                launch_receiver(redirect.clone(), channel.protocol.clone(), &channel.address, interest, buffer, dispatcher.clone(), token.clone());
+                launch_receiver(redirect.clone(), channel, interest, buffer, dispatcher.clone(), status.clone(), registry.clone(), token.clone());
             }
         }
         if let Some(sender) = config.sender {
             for channel in sender.channels {
-                let regex = match Regex::new(&channel.interest) {
-                    Ok(re) => re,
-                    Err(_) => continue,
+                let interest = match channel.interest.build() {
+                    Ok(interest) => interest,
+                    Err(e) => {
+                        let _ = status.send(Status::Failed(channel.protocol.clone(), channel.address.clone(), e));
+                        continue;
+                    },
                 };
-                let interest = Interest::new(regex);
-                launch_sender(if adv { receiver.clone() } else { None }, channel.protocol, &channel.address, interest, buffer, dispatcher.clone(), token.clone());
+                launch_sender(pool.clone(), if adv { receiver.clone() } else { None }, &channel, interest, buffer, dispatcher.clone(), status.clone(), registry.clone(), token.clone());
             }
         }
     }
     Ok(())
 }
 
-fn launch_redirect(mut rx: mpsc::Receiver<Event>, disp_tx: mpsc::Sender<Command>, buffer: usize, token: CancellationToken) {
+// Launches the senders advertised by the peers, reporting the invalid advertisements as failures of the receiving channel.
+fn launch_redirect(mut rx: mpsc::Receiver<Advertisement>, pool: Pool, disp_tx: mpsc::Sender<Command>, buffer: usize, status: mpsc::UnboundedSender<Status>, registry: Arc<Registry>, token: CancellationToken) {
     tokio::spawn(async move {
         loop {
             select! {
                 _ = token.cancelled() => break,
                 option = rx.recv() => {
                     match option {
-                        Some(event) => {
-                            if let Ok(string) = std::str::from_utf8(&event.data) {
-                                if let Ok(recv) = toml::from_str::<Receiver>(string) {
-                                    for channel in recv.node.channels {
-                                        if let Ok(re) = Regex::new(&channel.interest) {
-                                            launch_sender(None, channel.protocol, &channel.address, Interest::new(re), buffer, disp_tx.clone(), token.clone());
-                                        }
-                                    }
+                        Some((protocol, addr, event)) => {
+                            let recv = std::str::from_utf8(&event.data)
+                                .map_err(|e| Error::Codec(io::Error::new(io::ErrorKind::InvalidData, e)))
+                                .and_then(|string| toml::from_str::<Receiver>(string).map_err(Error::from));
+                            let recv = match recv {
+                                Ok(recv) => recv,
+                                Err(e) => {
+                                    let _ = status.send(Status::Failed(protocol, addr, e));
+                                    continue;
+                                },
+                            };
+                            for channel in recv.node.channels {
+                                match channel.interest.build() {
+                                    Ok(interest) => launch_sender(pool.clone(), None, &channel, interest, buffer, disp_tx.clone(), status.clone(), registry.clone(), token.clone()),
+                                    Err(e) => {
+                                        let _ = status.send(Status::Failed(channel.protocol.clone(), channel.address.clone(), e));
+                                    },
                                 }
                             }
                         },
@@ -127,88 +301,233 @@ fn launch_redirect(mut rx: mpsc::Receiver<Event>, disp_tx: mpsc::Sender<Command>
     });
 }
 
-fn launch_receiver(send: Option<(Interest, mpsc::Sender<Event>)>, protocol: Protocol, address: &str, interest: Interest, buffer: usize, disp_tx: mpsc::Sender<Command>, token: CancellationToken) {
-    let addr = address.to_string().clone();
+#[allow(clippy::too_many_arguments)]
+fn launch_receiver(send: Option<(Interest, mpsc::Sender<Advertisement>)>, channel: &Channel, interest: Interest, buffer: usize, disp_tx: mpsc::Sender<Command>, status: mpsc::UnboundedSender<Status>, registry: Arc<Registry>, token: CancellationToken) {
+    let (protocol, addr, tls_config) = (channel.protocol.clone(), channel.address.clone(), channel.tls.clone().unwrap_or_default());
+    let (channel, idle_timeout) = (channel.clone(), channel.idle_timeout_ms.map(Duration::from_millis));
     tokio::spawn(async move {
         let (tx, mut rx) = mpsc::channel(buffer);
-        match protocol {
-            Protocol::TCP => {
-                tcp::new_receiver(addr, tx, token.clone()).await.unwrap();
+        let result = match protocol {
+            Protocol::TCP if channel.propagate => tcp::new_link_receiver(addr.clone(), channel.framing(), interest.clone(), tx, disp_tx.clone(), buffer, idle_timeout, token.clone()).await,
+            Protocol::TCP => match channel.duplex.as_ref().map(InterestSpec::build) {
+                Some(Ok(allowed)) => tcp::new_duplex_receiver(addr.clone(), channel.framing(), allowed, tx, disp_tx.clone(), buffer, idle_timeout, token.clone()).await,
+                Some(Err(e)) => Err(e),
+                None => tcp::new_receiver(addr.clone(), channel.framing(), tx, idle_timeout, token.clone()).await,
+            },
+            Protocol::UDP => udp::new_receiver(addr.clone(), tx, idle_timeout, token.clone()).await,
+            Protocol::DATAGRAM => udp::new_datagram_receiver(addr.clone(), tx, token.clone()).await,
+            Protocol::TLS => match tls_config.server_config() {
+                Ok(config) => tls::new_receiver(addr.clone(), config, channel.framing(), tx, idle_timeout, token.clone()).await,
+                Err(e) => Err(e),
             },
-            Protocol::UDP => {
-                udp::new_receiver(addr, tx, token.clone()).await.unwrap();
+            #[cfg(unix)]
+            Protocol::UNIX => unix::new_receiver(&addr, channel.framing(), tx, idle_timeout, token.clone()).await,
+            #[cfg(not(unix))]
+            Protocol::UNIX => Err(Error::Bind(io::ErrorKind::Unsupported.into())),
+            Protocol::WS => ws::new_receiver(addr.clone(), tx, token.clone()).await,
+            Protocol::QUIC => match tls_config.server_config() {
+                Ok(config) => quic::new_receiver(addr.clone(), config, tx, token.clone()).await,
+                Err(e) => Err(e),
+            },
+            Protocol::Custom(ref name) => match registry.get(name) {
+                Ok(custom) => transport::new_receiver(custom, &channel, tx, token.clone()).await,
+                Err(e) => Err(e),
             },
         };
-        if let Some((adv, tx)) = send {
-            loop {
-                select! {
-                    _ = token.cancelled() => break,
-                    message = rx.recv() => {
-                        match message {
-                            Some(event) => {
+        if let Err(e) = result {
+            let _ = status.send(Status::Failed(protocol, addr, e));
+            return;
+        }
+        let _ = status.send(Status::Listening(protocol.clone(), addr.clone()));
+        let result = loop {
+            select! {
+                _ = token.cancelled() => break Ok(()),
+                message = rx.recv() => {
+                    match message {
+                        Some(event) => {
+                            if let Some((adv, tx)) = &send {
                                 if adv.is_valid(&event) {
-                                    let _ = tx.send(event).await;
-                                } else if interest.is_valid(&event) {
-                                    disp_tx.send(Command::Forward(event)).await.unwrap();
+                                    let _ = tx.send((protocol.clone(), addr.clone(), event)).await;
+                                    continue;
                                 }
-                            },
-                            None => break,
-                        }
+                            }
+                            if is_system(&event) || interest.is_valid(&event) {
+                                if let Err(e) = disp_tx.send(Command::Forward(event)).await {
+                                    break Err(Error::from(e));
+                                }
+                            }
+                        },
+                        None => break Ok(()),
                     }
                 }
             }
-        } else {
+        };
+        if let Err(e) = result {
+            let _ = status.send(Status::Failed(protocol, addr, e));
+        }
+    });
+}
+
+// Multiplexes the channel onto the connection to its peer, which is established only by the first channel with its address.
+#[allow(clippy::too_many_arguments)]
+fn launch_sender(pool: Pool, recv: Option<Arc<Receiver>>, channel: &Channel, interest: Interest, buffer: usize, disp_tx: mpsc::Sender<Command>, status: mpsc::UnboundedSender<Status>, registry: Arc<Registry>, token: CancellationToken) {
+    let (protocol, addr, tls_config) = (channel.protocol.clone(), channel.address.clone(), channel.tls.clone().unwrap_or_default());
+    if channel.propagate && protocol == Protocol::TCP {
+        return launch_link(addr, channel.framing(), interest, buffer, disp_tx, status, token);
+    }
+    let channel = channel.clone();
+    let ws_mode = channel.ws_mode.unwrap_or_default();
+    tokio::spawn(async move {
+        let key = (protocol.clone(), addr.clone());
+        let mut peers = pool.lock().await;
+        if let Some(peer) = peers.get_mut(&key) {
+            if peer.settings != Settings::new(&channel) {
+                let _ = status.send(Status::Failed(protocol, addr, Error::ConflictingChannel));
+                return;
+            }
+            peer.interests.push(interest);
+            if let Some(handle) = &peer.handle {
+                if let Err(e) = handle.set_interest(Interest::Any(peer.interests.clone())).await {
+                    let _ = status.send(Status::Failed(protocol, addr, e));
+                }
+            }
+            return;
+        }
+        // The peer is reserved, so that the connection is established without holding the pool.
+        peers.insert(key.clone(), Peer { handle: None, interests: vec![interest], settings: Settings::new(&channel) });
+        drop(peers);
+        let result: Result<()> = async {
+            let (tx, rx) = mpsc::channel(buffer);
+            match protocol {
+                Protocol::TCP => match &channel.duplex {
+                    Some(spec) => {
+                        let (back, (back_tx, back_rx)) = (spec.build()?, mpsc::channel(buffer));
+                        tcp::new_duplex_sender(addr.clone(), channel.framing(), spec, rx, back_tx).await?;
+                        tokio::spawn(forward_back(back_rx, back, disp_tx.clone()));
+                        let _ = status.send(Status::Connected(protocol.clone(), addr.clone()));
+                    },
+                    None => {
+                        let state = tcp::new_reconnecting_sender(addr.clone(), channel.framing(), rx, Backoff::default(), buffer, token.clone());
+                        tokio::spawn(report_state(state, protocol.clone(), addr.clone(), status.clone(), disp_tx.clone()));
+                    },
+                },
+                Protocol::UDP => {
+                    udp::new_sender(addr.clone(), rx).await?;
+                    let _ = status.send(Status::Connected(protocol.clone(), addr.clone()));
+                },
+                Protocol::DATAGRAM => {
+                    udp::new_datagram_sender(addr.clone(), rx).await?;
+                    let _ = status.send(Status::Connected(protocol.clone(), addr.clone()));
+                },
+                Protocol::TLS => {
+                    let (server_name, config) = (tls_config.server_name(&addr)?, tls_config.client_config()?);
+                    let state = tls::new_reconnecting_sender(addr.clone(), server_name, config, channel.framing(), rx, Backoff::default(), buffer, token.clone());
+                    tokio::spawn(report_state(state, protocol.clone(), addr.clone(), status.clone(), disp_tx.clone()));
+                },
+                #[cfg(unix)]
+                Protocol::UNIX => {
+                    unix::new_sender(&addr, channel.framing(), rx).await?;
+                    let _ = status.send(Status::Connected(protocol.clone(), addr.clone()));
+                },
+                #[cfg(not(unix))]
+                Protocol::UNIX => return Err(Error::Connect(io::ErrorKind::Unsupported.into())),
+                Protocol::WS if addr.starts_with("ws://") => {
+                    ws::new_sender(&addr, ws_mode, rx).await?;
+                    let _ = status.send(Status::Connected(protocol.clone(), addr.clone()));
+                },
+                Protocol::WS => {
+                    ws::new_publisher(addr.clone(), ws_mode, rx, buffer, token.clone()).await?;
+                    let _ = status.send(Status::Listening(protocol.clone(), addr.clone()));
+                },
+                Protocol::QUIC => {
+                    let (server_name, config) = (tls_config.server_name(&addr)?, tls_config.client_config()?);
+                    quic::new_sender(addr.clone(), &server_name.to_str(), config, rx).await?;
+                    let _ = status.send(Status::Connected(protocol.clone(), addr.clone()));
+                },
+                Protocol::Custom(ref name) => {
+                    transport::new_sender(registry.get(name)?, &channel, rx).await?;
+                    let _ = status.send(Status::Connected(protocol.clone(), addr.clone()));
+                },
+            };
+            // The pool stays locked until the subscription is sent, so that no interest change can precede it.
+            let mut peers = pool.lock().await;
+            let peer = peers.get_mut(&key).expect("reserved peer");
+            let (sub, mut arc_rx) = Subscription::new(Interest::Any(peer.interests.clone()), buffer, Backpressure::DropNewest);
+            let sub = sub.advertised(false);
+            peer.handle = Some(sub.handle(disp_tx.clone()));
+            disp_tx.send(Command::Subscribe(sub)).await?;
+            drop(peers);
+            if let Some(receiver) = recv {
+                let string = toml::to_string(receiver.as_ref()).map_err(|e| Error::Codec(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+                let event = Event::new(&receiver.adv_topic, Bytes::from(string));
+                tx.send(Arc::new(event)).await?;
+            }
             loop {
                 select! {
                     _ = token.cancelled() => break,
-                    message = rx.recv() => {
-                        match message {
+                    dispatch = arc_rx.recv() => {
+                        match dispatch {
                             Some(event) => {
-                                if interest.is_valid(&event) {
-                                    disp_tx.send(Command::Forward(event)).await.unwrap();
-                                }
+                                tx.send(event).await?;
                             },
                             None => break,
                         }
                     }
                 }
             }
+            Ok(())
+        }.await;
+        let peer = pool.lock().await.remove(&key);
+        if let Err(e) = result {
+            // Every channel sharing the connection fails with it.
+            for _ in 0..peer.map_or(1, |peer| peer.interests.len()) {
+                let _ = status.send(Status::Failed(protocol.clone(), addr.clone(), e.clone()));
+            }
         }
-
     });
 }
 
-fn launch_sender(recv: Option<Arc<Receiver>>, protocol: Protocol, address: &str, interest: Interest, buffer: usize, disp_tx: mpsc::Sender<Command>, token: CancellationToken) {
-    let addr = address.to_string().clone();
+// Connects a link to the peer, which is not pooled since it carries its own interests.
+fn launch_link(addr: String, framing: FramingConfig, interest: Interest, buffer: usize, disp_tx: mpsc::Sender<Command>, status: mpsc::UnboundedSender<Status>, token: CancellationToken) {
     tokio::spawn(async move {
-        let (sub, mut arc_rx) = Subscription::new(interest, buffer);
         let (tx, rx) = mpsc::channel(buffer);
-        match protocol {
-            Protocol::TCP => {
-                tcp::new_sender(addr, rx).await.unwrap();
+        match tcp::new_link_sender(addr.clone(), framing, interest.clone(), tx, disp_tx.clone(), buffer, token).await {
+            Ok(()) => {
+                let _ = status.send(Status::Connected(Protocol::TCP, addr));
+                forward_back(rx, interest, disp_tx).await;
             },
-            Protocol::UDP => {
-                udp::new_sender(addr, rx).await.unwrap();
+            Err(e) => {
+                let _ = status.send(Status::Failed(Protocol::TCP, addr, e));
             },
+        }
+    });
+}
+
+// Forwards to the `Dispatcher` the `Event`s received by a sender, which match the given interest.
+async fn forward_back(mut rx: mpsc::Receiver<Event>, interest: Interest, disp_tx: mpsc::Sender<Command>) {
+    while let Some(event) = rx.recv().await {
+        if interest.is_valid(&event) && disp_tx.send(Command::Forward(event)).await.is_err() {
+            break;
+        }
+    }
+}
+
+// Reports the changes of the state of a reconnecting sender as `Status`es, notifying locally the lost connections.
+async fn report_state(mut state: watch::Receiver<ConnectionState>, protocol: Protocol, address: String, status: mpsc::UnboundedSender<Status>, disp_tx: mpsc::Sender<Command>) {
+    let mut connected = false;
+    while state.changed().await.is_ok() {
+        let report = match *state.borrow_and_update() {
+            ConnectionState::Connected(_) => Status::Connected(protocol.clone(), address.clone()),
+            ConnectionState::Disconnected => Status::Disconnected(protocol.clone(), address.clone()),
+            ConnectionState::Connecting => continue,
         };
-        disp_tx.send(Command::Subscribe(sub)).await.unwrap();
-        if let Some(receiver) = recv {
-            let string = toml::to_string(receiver.as_ref()).unwrap();
-            let event = Event::new(&receiver.adv_topic, Bytes::from(string));
-            tx.send(event).await.unwrap();
+        let lost = connected && matches!(report, Status::Disconnected(..));
+        connected = matches!(report, Status::Connected(..));
+        if lost {
+            let _ = disp_tx.send(Command::Forward(connection_lost_event(&address))).await;
         }
-        loop {
-            select! {
-                _ = token.cancelled() => break,
-                dispatch = arc_rx.recv() => {
-                    match dispatch {
-                        Some(event) => {
-                            tx.send(event.as_ref().clone()).await.unwrap();
-                        },
-                        None => break,
-                    }
-                }
-            }
+        if status.send(report).is_err() {
+            break;
         }
-    });
-}
\ No newline at end of file
+    }
+}
@@ -0,0 +1,125 @@
+//! This module defines the error type shared by the whole crate.
+
+use std::{fmt::Display, io, sync::Arc};
+
+use tokio::sync::mpsc::error::SendError;
+
+use super::Event;
+
+/// Alias for results whose error is the crate `Error`.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned by the functions of the crate.
+#[derive(Debug)]
+pub enum Error {
+    /// A listener could not be bound to the address.
+    Bind(io::Error),
+    /// A connection to the address could not be established.
+    Connect(io::Error),
+    /// The address could not be resolved.
+    Address,
+    /// An `Event` could not be serialized or deserialized.
+    Codec(io::Error),
+    /// The frame of an `Event`, of the given size, exceeds the maximum size allowed by the transport.
+    FrameTooLarge { size: usize, max: usize },
+    /// A configuration file could not be read.
+    Io(io::Error),
+    /// A configuration file could not be parsed.
+    Config(toml::de::Error),
+    /// A regex pattern is invalid.
+    Regex(regex::Error),
+    /// The TLS settings are invalid.
+    Tls(rustls::Error),
+    /// No `Transport` is registered with the name of the protocol.
+    UnknownProtocol(String),
+    /// The channel has the protocol and the address of another one, sharing its connection, but different settings.
+    ConflictingChannel,
+    /// The `Dispatcher`, or the task on the other end of a channel, is no longer running.
+    DispatcherClosed,
+    /// The timeout expired before the operation completed, and contains the `Event`s received so far.
+    Timeout(Vec<Arc<Event>>),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bind(e) => write!(f, "bind failed: {}", e),
+            Self::Connect(e) => write!(f, "connection failed: {}", e),
+            Self::Address => write!(f, "address not found"),
+            Self::Codec(e) => write!(f, "codec error: {}", e),
+            Self::FrameTooLarge { size, max } => write!(f, "frame of {} bytes exceeds the maximum of {} bytes", size, max),
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Config(e) => write!(f, "invalid configuration: {}", e),
+            Self::Regex(e) => write!(f, "invalid regex: {}", e),
+            Self::Tls(e) => write!(f, "invalid tls settings: {}", e),
+            Self::UnknownProtocol(name) => write!(f, "unknown protocol: {}", name),
+            Self::ConflictingChannel => write!(f, "settings differ from the channel sharing the connection"),
+            Self::DispatcherClosed => write!(f, "dispatcher closed"),
+            Self::Timeout(events) => write!(f, "timed out after {} events", events.len()),
+        }
+    }
+}
+
+/// The wrapped `io::Error`s are copied with only their kind and message.
+impl Clone for Error {
+    fn clone(&self) -> Self {
+        let copy = |e: &io::Error| io::Error::new(e.kind(), e.to_string());
+        match self {
+            Self::Bind(e) => Self::Bind(copy(e)),
+            Self::Connect(e) => Self::Connect(copy(e)),
+            Self::Address => Self::Address,
+            Self::Codec(e) => Self::Codec(copy(e)),
+            Self::FrameTooLarge { size, max } => Self::FrameTooLarge { size: *size, max: *max },
+            Self::Io(e) => Self::Io(copy(e)),
+            Self::Config(e) => Self::Config(e.clone()),
+            Self::Regex(e) => Self::Regex(e.clone()),
+            Self::Tls(e) => Self::Tls(e.clone()),
+            Self::UnknownProtocol(name) => Self::UnknownProtocol(name.clone()),
+            Self::ConflictingChannel => Self::ConflictingChannel,
+            Self::DispatcherClosed => Self::DispatcherClosed,
+            Self::Timeout(events) => Self::Timeout(events.clone()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Bind(e) | Self::Connect(e) | Self::Codec(e) | Self::Io(e) => Some(e),
+            Self::Config(e) => Some(e),
+            Self::Regex(e) => Some(e),
+            Self::Tls(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Self::Config(e)
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(e: regex::Error) -> Self {
+        Self::Regex(e)
+    }
+}
+
+impl From<rustls::Error> for Error {
+    fn from(e: rustls::Error) -> Self {
+        Self::Tls(e)
+    }
+}
+
+impl<T> From<SendError<T>> for Error {
+    fn from(_: SendError<T>) -> Self {
+        Self::DispatcherClosed
+    }
+}
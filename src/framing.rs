@@ -1,26 +1,657 @@
 //! This module offers functions and types for handling the the framing of streams.
+//!
+//! Each frame is prefixed by its length, as a 4 bytes little endian integer, and contains an `Event` serialized in the
+//! `Format` of the channel. The frames in the default `Format::Bincode` are versioned as described below, while the
+//! ones in the other formats contain just the serialization of the `Event`, so that peers written in other languages
+//! can use their usual libraries. The `Format::Protobuf` frames follow `PROTO_SCHEMA`.
+//!
+//! Each versioned frame starts with `MAGIC` followed by the `WIRE_VERSION` byte, and then the bincode serialization of the `Event`.
+//! Frames without `MAGIC` are the ones sent by legacy nodes, which serialize only `topic`, `timestamp` and `data`.
+//! Frames of version 2 lack the `id`, `origin`, `hops` and `ttl` of the `Event`, which are set as for a new one.
+//! The `hops` of each decoded `Event` are increased, since it has crossed one more link.
+//!
+//! Frames of any format can be compressed as a whole, in which case `MAGIC` is followed by `COMPRESSED_FRAME` instead of the version,
+//! then by the id of the `Algorithm` and the compressed frame. A node compresses the frames it writes only once its peer
+//! accepted the `Compression` offered with a control `Event` on `COMPRESSION_OFFER_TOPIC`, replying on
+//! `COMPRESSION_ACCEPT_TOPIC`, so peers unaware of the compression keep receiving plain frames.
+//!
+//! Frames longer than the maximum length of the channel, `DEFAULT_MAX_FRAME_LENGTH` by default, are rejected with an
+//! `Error::FrameTooLarge`, also once decompressed, closing the connection that received them.
 
-use super::Event;
+use std::{fmt, io::{Error, ErrorKind, Read}, pin::Pin, sync::{Arc, OnceLock, atomic::{AtomicBool, AtomicU64, Ordering}}};
 
+use super::{Event, HeaderValue, Headers, default_ttl, random_u64};
+
+use bytes::{Buf, Bytes, BytesMut, BufMut};
+use chrono::{DateTime, Utc};
+use prost::Message;
+use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
-use tokio_serde::{SymmetricallyFramed, formats::SymmetricalBincode};
+use tokio_util::codec::{Decoder, Encoder, Framed};
+use tokio_serde::{Serializer, Deserializer};
+
+/// Bytes identifying a versioned frame. Legacy frames start with the length of the topic, which can never match them.
+pub const MAGIC: [u8; 4] = [0xC0, 0x33, 0x0D, 0xE5];
+/// Version of the wire format written by this node.
+pub const WIRE_VERSION: u8 = 3;
+/// Version of the wire format of the nodes not tracking the identity and the hops of the `Event`s.
+pub const V2_VERSION: u8 = 2;
+/// Version of the wire format of legacy nodes, whose frames do not start with `MAGIC`.
+pub const LEGACY_VERSION: u8 = 1;
+/// Byte following `MAGIC` in place of the version in the compressed frames.
+pub const COMPRESSED_FRAME: u8 = 0x80;
+
+/// Topic of the control `Event`s offering to compress the frames, containing the name of the `Algorithm`.
+pub const COMPRESSION_OFFER_TOPIC: &str = "$compression/offer";
+/// Topic of the control `Event`s accepting the compression offered by the peer, containing the name of the `Algorithm`.
+pub const COMPRESSION_ACCEPT_TOPIC: &str = "$compression/accept";
+/// Default maximum length, in bytes, of the frames, and of the decompressed ones.
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 64 * 1024 * 1024;
+/// Default size, in bytes, from which the frames are compressed.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 4096;
+
+/// Serialization formats of the `Event`s in the frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// Versioned bincode serialization, able to read the frames of older nodes too.
+    #[default]
+    Bincode,
+    /// MessagePack serialization, with the fields of the `Event` as a map.
+    MessagePack,
+    /// CBOR serialization.
+    Cbor,
+    /// JSON serialization, as the text messages of the `WS` protocol.
+    Json,
+    /// Protobuf serialization, following `PROTO_SCHEMA`.
+    Protobuf,
+}
+
+/// Schema of the `Event`s in the `Format::Protobuf` frames.
+///
+/// The timestamp is in microseconds since the Unix epoch, and a missing `ttl` stands for `DEFAULT_TTL`.
+pub const PROTO_SCHEMA: &str = r#"syntax = "proto3";
+
+message Event {
+    string topic = 1;
+    int64 timestamp = 2;
+    bytes data = 3;
+    optional uint64 correlation_id = 4;
+    map<string, HeaderValue> headers = 5;
+    uint64 id = 6;
+    optional uint64 origin = 7;
+    uint32 hops = 8;
+    optional uint32 ttl = 9;
+}
+
+message HeaderValue {
+    oneof value {
+        string text = 1;
+        bytes bytes = 2;
+    }
+}
+"#;
+
+/// Compression algorithms of the frames.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Zstd,
+    Lz4,
+}
+
+impl Algorithm {
+    /// Returns the name of the algorithm, as in the configuration files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Lz4 => "lz4",
+        }
+    }
+
+    /// Returns the algorithm with the given name, if supported.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Zstd, Self::Lz4].into_iter().find(|algorithm| algorithm.name() == name)
+    }
+
+    // Identifier of the algorithm in the compressed frames.
+    fn id(self) -> u8 {
+        match self {
+            Self::Zstd => 1,
+            Self::Lz4 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        [Self::Zstd, Self::Lz4].into_iter().find(|algorithm| algorithm.id() == id)
+    }
+}
+
+/// Compression of the frames written to a peer, configured per channel.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Compression {
+    pub algorithm: Algorithm,
+    /// Size, in bytes, from which the frames are compressed, as the smaller ones rarely benefit from it.
+    #[serde(default = "default_threshold")]
+    pub threshold: usize,
+}
+
+// Returns the threshold of the `Compression`s whose configuration does not contain it.
+fn default_threshold() -> usize {
+    DEFAULT_COMPRESSION_THRESHOLD
+}
+
+/// Framing of the streams of a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FramingConfig {
+    /// Serialization of the `Event`s, which must be the same on both the sides of the connection.
+    pub format: Format,
+    /// Compression of the frames written to the peer, if it accepts it.
+    pub compression: Option<Compression>,
+    /// Maximum length, in bytes, of the frames, beyond which they are rejected and the connection is closed.
+    pub max_frame_length: usize,
+}
+
+impl Default for FramingConfig {
+    fn default() -> Self {
+        Self {
+            format: Format::default(),
+            compression: None,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+}
+
+// Number of frames rejected so far for exceeding their maximum length.
+static OVERSIZE_FRAMES: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the number of frames rejected so far, received or sent, for exceeding their maximum length.
+pub fn oversize_frames() -> u64 {
+    OVERSIZE_FRAMES.load(Ordering::Relaxed)
+}
+
+// Builds the error of a frame exceeding its maximum length, counting it as rejected.
+fn too_large(size: usize, max: usize) -> Error {
+    OVERSIZE_FRAMES.fetch_add(1, Ordering::Relaxed);
+    Error::new(ErrorKind::InvalidData, crate::Error::FrameTooLarge { size, max })
+}
+
+/// Returns `true` if the error of a framed stream is an `Error::FrameTooLarge`, `false` otherwise.
+pub fn is_too_large(e: &Error) -> bool {
+    matches!(e.get_ref().and_then(|inner| inner.downcast_ref::<crate::Error>()), Some(crate::Error::FrameTooLarge { .. }))
+}
+
+/// Codec delimiting the frames with their length, as a 4 bytes little endian integer.
+///
+/// The frames exceeding the maximum length are rejected with an `Error::FrameTooLarge` as soon as their length is
+/// read, before buffering them, after which the framed stream ends.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameCodec {
+    max_frame_length: usize,
+}
+
+impl FrameCodec {
+    /// Creates a new `FrameCodec` instance with the given maximum length of the frames, at most `u32::MAX`.
+    pub fn new(max_frame_length: usize) -> Self {
+        Self {
+            max_frame_length: max_frame_length.min(u32::MAX as usize),
+        }
+    }
+
+    /// Returns the maximum length of the frames.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FRAME_LENGTH)
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = BytesMut;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, Error> {
+        let Some(head) = src.get(..4) else {
+            return Ok(None);
+        };
+        let size = u32::from_le_bytes(head.try_into().expect("4 bytes head")) as usize;
+        if size > self.max_frame_length {
+            return Err(too_large(size, self.max_frame_length));
+        }
+        if src.len() < 4 + size {
+            src.reserve(4 + size - src.len());
+            return Ok(None);
+        }
+        src.advance(4);
+        Ok(Some(src.split_to(size)))
+    }
+}
+
+impl Encoder<Bytes> for FrameCodec {
+    type Error = Error;
+
+    fn encode(&mut self, frame: Bytes, dst: &mut BytesMut) -> Result<(), Error> {
+        if frame.len() > self.max_frame_length {
+            return Err(too_large(frame.len(), self.max_frame_length));
+        }
+        dst.reserve(4 + frame.len());
+        dst.put_u32_le(frame.len() as u32);
+        dst.put_slice(&frame);
+        Ok(())
+    }
+}
 
 /// Alias for nested framed types.
-pub type FramedStream<T> = SymmetricallyFramed<Framed<T, LengthDelimitedCodec>, Event, SymmetricalBincode<Event>>;
+///
+/// The `Event`s are written as `Arc<Event>`, so that the ones dispatched to several peers are serialized only once.
+pub type FramedStream<T> = tokio_serde::Framed<Framed<T, FrameCodec>, Event, Arc<Event>, EventCodec>;
 
-/// Returns the framed version of the input stream, with `LenghtDelimitedCodec` and `SymmetricalBincode` serialization.
+/// Returns the framed version of the input stream, with `FrameCodec` and `EventCodec` serialization.
 pub fn frame_stream<T: AsyncRead + AsyncWrite>(stream: T) -> FramedStream<T> {
-    let inner = Framed::new(stream, LengthDelimitedCodec::builder().little_endian().length_field_length(4).max_frame_length(4_294_967_295).new_codec());
-    let framed = FramedStream::new(inner, SymmetricalBincode::<Event>::default());
-    framed
+    frame_stream_with(stream, EventCodec::default())
+}
+
+/// Returns the framed version of the input stream, with `FrameCodec` and the given `EventCodec`, whose maximum
+/// length of the frames it follows, and whose clones can then negotiate the compression with the peer.
+pub fn frame_stream_with<T: AsyncRead + AsyncWrite>(stream: T, codec: EventCodec) -> FramedStream<T> {
+    FramedStream::new(frame_string_with(stream, codec.max_frame_length), codec)
 }
 
 /// Alias for nested framed types.
-pub type FramedString<T> = Framed<T, LengthDelimitedCodec>;
+pub type FramedString<T> = Framed<T, FrameCodec>;
 
-/// Returns the framed version of the input stream, with `LenghtDelimitedCodec` and `SymmetricalBincode` serialization.
+/// Returns the framed version of the input stream, with `FrameCodec`.
 pub fn frame_string<T: AsyncRead + AsyncWrite>(stream: T) -> FramedString<T> {
-    let framed = Framed::new(stream, LengthDelimitedCodec::builder().little_endian().length_field_length(4).max_frame_length(4_294_967_295).new_codec());
-    framed
-}
\ No newline at end of file
+    Framed::new(stream, length_codec())
+}
+
+/// Returns the framed version of the input stream, with `FrameCodec` and the given maximum length of the frames.
+pub fn frame_string_with<T: AsyncRead + AsyncWrite>(stream: T, max_frame_length: usize) -> FramedString<T> {
+    Framed::new(stream, FrameCodec::new(max_frame_length))
+}
+
+/// Returns the `FrameCodec` delimiting the frames, for the streams that are only read or only written.
+pub fn length_codec() -> FrameCodec {
+    FrameCodec::default()
+}
+
+/// Returns the version of the wire format of the given frame, or `LEGACY_VERSION` if it has no `MAGIC`.
+pub fn wire_version(frame: &[u8]) -> u8 {
+    match frame.strip_prefix(&MAGIC) {
+        Some(rest) => rest.first().copied().unwrap_or(0),
+        None => LEGACY_VERSION,
+    }
+}
+
+/// Serializes an `Event` as a versioned frame, without the length prefix.
+pub fn encode(event: &Event) -> Result<Bytes, Error> {
+    let mut buf = BytesMut::new();
+    buf.put_slice(&MAGIC);
+    buf.put_u8(WIRE_VERSION);
+    let mut writer = buf.writer();
+    bincode::serialize_into(&mut writer, event).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    Ok(writer.into_inner().freeze())
+}
+
+/// Serializes a shared `Event` in the default versioned frame, which is computed only once for all its senders.
+pub fn encode_shared(event: &Arc<Event>) -> Result<Bytes, Error> {
+    event.frame.get_or_encode((Format::Bincode, None), || encode(event))
+}
+
+// Frame of an `Event`, cached along with the `Format` and `Compression` it was serialized with, so that the senders
+// fanning out the same `Arc<Event>` to several peers serialize it once. Only the first serialization is cached, and the
+// clones of the `Event` start without any frame, since they can be modified.
+#[derive(Default)]
+pub(crate) struct FrameCache(OnceLock<((Format, Option<Compression>), Bytes)>);
+
+impl FrameCache {
+    // Returns the cached frame if serialized the same way, or else the one built by `encode`, caching it if it is the first.
+    fn get_or_encode(&self, key: (Format, Option<Compression>), encode: impl FnOnce() -> Result<Bytes, Error>) -> Result<Bytes, Error> {
+        if let Some((cached, frame)) = self.0.get() {
+            if *cached == key {
+                return Ok(frame.clone());
+            }
+            return encode();
+        }
+        let frame = encode()?;
+        let _ = self.0.set((key, frame.clone()));
+        Ok(frame)
+    }
+}
+
+impl Clone for FrameCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for FrameCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FrameCache").field(&self.0.get().map(|(_, frame)| frame.len())).finish()
+    }
+}
+
+/// Compresses a frame as a whole with the given algorithm.
+pub fn compress(frame: &[u8], algorithm: Algorithm) -> Result<Bytes, Error> {
+    let mut buf = BytesMut::new();
+    buf.put_slice(&MAGIC);
+    buf.put_u8(COMPRESSED_FRAME);
+    buf.put_u8(algorithm.id());
+    match algorithm {
+        Algorithm::Zstd => buf.put_slice(&zstd::bulk::compress(frame, 0)?),
+        Algorithm::Lz4 => buf.put_slice(&lz4_flex::compress_prepend_size(frame)),
+    }
+    Ok(buf.freeze())
+}
+
+// Decompresses a compressed frame, without `MAGIC` and `COMPRESSED_FRAME`, rejecting it if it expands beyond `max` bytes.
+fn decompress(compressed: &[u8], max: usize) -> Result<Vec<u8>, Error> {
+    let (id, payload) = compressed.split_first().ok_or_else(|| Error::new(ErrorKind::InvalidData, "missing compression algorithm"))?;
+    match Algorithm::from_id(*id) {
+        Some(Algorithm::Zstd) => {
+            let mut buf = match zstd::zstd_safe::get_frame_content_size(payload) {
+                Ok(Some(size)) if size > max as u64 => return Err(too_large(usize::try_from(size).unwrap_or(usize::MAX), max)),
+                Ok(Some(size)) => Vec::with_capacity(size as usize),
+                _ => Vec::new(),
+            };
+            // The content size is optional, so the decoder stops just past the maximum.
+            zstd::stream::read::Decoder::with_buffer(payload)?.take(max as u64 + 1).read_to_end(&mut buf)?;
+            if buf.len() > max {
+                return Err(too_large(buf.len(), max));
+            }
+            Ok(buf)
+        },
+        Some(Algorithm::Lz4) => {
+            let (size, payload) = lz4_flex::block::uncompressed_size(payload).map_err(invalid)?;
+            if size > max {
+                return Err(too_large(size, max));
+            }
+            lz4_flex::decompress(payload, size).map_err(invalid)
+        },
+        None => Err(Error::new(ErrorKind::InvalidData, format!("unsupported compression algorithm {}", id))),
+    }
+}
+
+/// Deserializes an `Event` from a versioned, legacy or compressed frame, without the length prefix.
+pub fn decode(frame: &[u8]) -> Result<Event, Error> {
+    decode_with(frame, Format::Bincode)
+}
+
+/// Serializes an `Event` as a frame in the given `Format`, without the length prefix.
+pub fn encode_with(event: &Event, format: Format) -> Result<Bytes, Error> {
+    match format {
+        Format::Bincode => return encode(event),
+        Format::MessagePack => rmp_serde::to_vec_named(event).map_err(invalid),
+        Format::Cbor => {
+            let mut buf = Vec::new();
+            ciborium::into_writer(event, &mut buf).map(|_| buf).map_err(invalid)
+        },
+        Format::Json => serde_json::to_vec(event).map_err(invalid),
+        Format::Protobuf => Ok(ProtoEvent::from(event).encode_to_vec()),
+    }.map(Bytes::from)
+}
+
+/// Deserializes an `Event` from a frame in the given `Format`, or from a compressed one, without the length prefix.
+pub fn decode_with(frame: &[u8], format: Format) -> Result<Event, Error> {
+    decode_limited(frame, format, DEFAULT_MAX_FRAME_LENGTH)
+}
+
+// Deserializes an `Event` from a frame in the given format, whose decompressed length cannot exceed `max` bytes.
+fn decode_limited(frame: &[u8], format: Format, max: usize) -> Result<Event, Error> {
+    if wire_version(frame) == COMPRESSED_FRAME {
+        let frame = decompress(&frame[MAGIC.len() + 1..], max)?;
+        if wire_version(&frame) == COMPRESSED_FRAME {
+            return Err(Error::new(ErrorKind::InvalidData, "nested compressed frame"));
+        }
+        return decode_limited(&frame, format, max);
+    }
+    let mut event = match format {
+        Format::Bincode => decode_versioned(frame)?,
+        Format::MessagePack => rmp_serde::from_slice(frame).map_err(invalid)?,
+        Format::Cbor => ciborium::from_reader(frame).map_err(invalid)?,
+        Format::Json => serde_json::from_slice(frame).map_err(invalid)?,
+        Format::Protobuf => ProtoEvent::decode(frame).map(Event::from).map_err(invalid)?,
+    };
+    event.hops = event.hops.saturating_add(1);
+    Ok(event)
+}
+
+// Deserializes an `Event` from a versioned or legacy bincode frame.
+fn decode_versioned(frame: &[u8]) -> Result<Event, Error> {
+    match wire_version(frame) {
+        WIRE_VERSION => bincode::deserialize::<Event>(&frame[MAGIC.len() + 1..]),
+        V2_VERSION => bincode::deserialize::<V2Event>(&frame[MAGIC.len() + 1..]).map(Event::from),
+        LEGACY_VERSION => bincode::deserialize::<LegacyEvent>(frame).map(Event::from),
+        version => return Err(Error::new(ErrorKind::InvalidData, format!("unsupported wire version {}", version))),
+    }.map_err(invalid)
+}
+
+// Wraps a serialization error as an `InvalidData` one.
+fn invalid(e: impl std::fmt::Display) -> Error {
+    Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+/// Returns `true` if the `Event` is a control one of the negotiation of the compression, `false` otherwise.
+pub fn is_negotiation(event: &Event) -> bool {
+    event.topic == COMPRESSION_OFFER_TOPIC || event.topic == COMPRESSION_ACCEPT_TOPIC
+}
+
+/// Returns the control `Event` accepting the compression offered by the peer, if the given `Event` offers a supported one.
+pub fn accept_compression(offer: &Event) -> Option<Event> {
+    if offer.topic != COMPRESSION_OFFER_TOPIC {
+        return None;
+    }
+    let algorithm = std::str::from_utf8(&offer.data).ok().and_then(Algorithm::from_name)?;
+    Some(Event::new(COMPRESSION_ACCEPT_TOPIC, Bytes::from_static(algorithm.name().as_bytes())).with_ttl(0))
+}
+
+/// Versioned serialization of `Event`s, able to read the frames of legacy nodes too.
+///
+/// The clones of a codec share the state of the negotiation of its `Compression`, if any, so that the handler of the
+/// connection can enable it once the peer accepts it.
+#[derive(Clone, Debug)]
+pub struct EventCodec {
+    format: Format,
+    compression: Option<Compression>,
+    max_frame_length: usize,
+    accepted: Arc<AtomicBool>,
+}
+
+impl Default for EventCodec {
+    fn default() -> Self {
+        Self::new(FramingConfig::default())
+    }
+}
+
+impl EventCodec {
+    /// Creates a new `EventCodec` instance with the given `FramingConfig`, compressing the frames once the peer accepts
+    /// its `Compression`, if any.
+    pub fn new(config: FramingConfig) -> Self {
+        Self {
+            format: config.format,
+            compression: config.compression,
+            max_frame_length: config.max_frame_length,
+            accepted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns the control `Event` offering the `Compression` to the peer, if any.
+    pub fn offer(&self) -> Option<Event> {
+        let compression = self.compression?;
+        Some(Event::new(COMPRESSION_OFFER_TOPIC, Bytes::from_static(compression.algorithm.name().as_bytes())).with_ttl(0))
+    }
+
+    /// Enables the `Compression` if the given control `Event` of the peer accepts the offered one.
+    pub fn accept(&self, event: &Event) {
+        let offered = self.compression.map(|compression| compression.algorithm.name().as_bytes());
+        if event.topic == COMPRESSION_ACCEPT_TOPIC && offered == Some(&event.data[..]) {
+            self.accepted.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns `true` if the frames are compressed, `false` otherwise.
+    pub fn is_compressing(&self) -> bool {
+        self.compression.is_some() && self.accepted.load(Ordering::Relaxed)
+    }
+}
+
+impl Serializer<Event> for EventCodec {
+    type Error = Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &Event) -> Result<Bytes, Self::Error> {
+        let frame = encode_with(item, self.format)?;
+        match self.compression {
+            Some(compression) if frame.len() >= compression.threshold && self.is_compressing() => compress(&frame, compression.algorithm),
+            _ => Ok(frame),
+        }
+    }
+}
+
+impl Serializer<Arc<Event>> for EventCodec {
+    type Error = Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &Arc<Event>) -> Result<Bytes, Self::Error> {
+        let compression = self.compression.filter(|_| self.is_compressing());
+        item.frame.get_or_encode((self.format, compression), || {
+            let frame = encode_with(item, self.format)?;
+            match compression {
+                Some(compression) if frame.len() >= compression.threshold => compress(&frame, compression.algorithm),
+                _ => Ok(frame),
+            }
+        })
+    }
+}
+
+impl Deserializer<Event> for EventCodec {
+    type Error = Error;
+
+    fn deserialize(self: Pin<&mut Self>, src: &BytesMut) -> Result<Event, Self::Error> {
+        decode_limited(src, self.format, self.max_frame_length)
+    }
+}
+
+// Event as serialized in the `Format::Protobuf` frames.
+#[derive(Clone, PartialEq, Message)]
+struct ProtoEvent {
+    #[prost(string, tag = "1")]
+    topic: String,
+    #[prost(int64, tag = "2")]
+    timestamp: i64,
+    #[prost(bytes = "bytes", tag = "3")]
+    data: Bytes,
+    #[prost(uint64, optional, tag = "4")]
+    correlation_id: Option<u64>,
+    #[prost(btree_map = "string, message", tag = "5")]
+    headers: std::collections::BTreeMap<String, ProtoHeaderValue>,
+    #[prost(uint64, tag = "6")]
+    id: u64,
+    #[prost(uint64, optional, tag = "7")]
+    origin: Option<u64>,
+    #[prost(uint32, tag = "8")]
+    hops: u32,
+    #[prost(uint32, optional, tag = "9")]
+    ttl: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoHeaderValue {
+    #[prost(oneof = "ProtoValue", tags = "1, 2")]
+    value: Option<ProtoValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum ProtoValue {
+    #[prost(string, tag = "1")]
+    Text(String),
+    #[prost(bytes = "bytes", tag = "2")]
+    Bytes(Bytes),
+}
+
+impl From<&Event> for ProtoEvent {
+    fn from(event: &Event) -> Self {
+        let headers = event.headers.iter().map(|(name, value)| {
+            let value = match value {
+                HeaderValue::Text(text) => ProtoValue::Text(text.clone()),
+                HeaderValue::Bytes(bytes) => ProtoValue::Bytes(bytes.clone()),
+            };
+            (name.clone(), ProtoHeaderValue { value: Some(value) })
+        }).collect();
+        Self {
+            topic: event.topic.clone(),
+            timestamp: event.timestamp.timestamp_micros(),
+            data: event.data.clone(),
+            correlation_id: event.correlation_id,
+            headers,
+            id: event.id,
+            origin: event.origin,
+            hops: event.hops.into(),
+            ttl: Some(event.ttl.into()),
+        }
+    }
+}
+
+impl From<ProtoEvent> for Event {
+    fn from(proto: ProtoEvent) -> Self {
+        let headers = proto.headers.into_iter().filter_map(|(name, value)| {
+            let value = match value.value? {
+                ProtoValue::Text(text) => HeaderValue::Text(text),
+                ProtoValue::Bytes(bytes) => HeaderValue::Bytes(bytes),
+            };
+            Some((name, value))
+        }).collect();
+        Self {
+            timestamp: DateTime::from_timestamp_micros(proto.timestamp).unwrap_or_default(),
+            correlation_id: proto.correlation_id,
+            headers,
+            // Peers not tracking the identity of the `Event`s leave it unset.
+            id: if proto.id == 0 { random_u64() } else { proto.id },
+            origin: proto.origin,
+            hops: proto.hops.min(u8::MAX.into()) as u8,
+            ttl: proto.ttl.map_or_else(default_ttl, |ttl| ttl.min(u8::MAX.into()) as u8),
+            frame: FrameCache::default(),
+            ..Self::new(&proto.topic, proto.data)
+        }
+    }
+}
+
+// Event as serialized by the nodes of version 2.
+#[derive(Deserialize)]
+struct V2Event {
+    topic: String,
+    timestamp: DateTime<Utc>,
+    data: Bytes,
+    correlation_id: Option<u64>,
+    headers: Headers,
+}
+
+impl From<V2Event> for Event {
+    fn from(v2: V2Event) -> Self {
+        Self {
+            timestamp: v2.timestamp,
+            correlation_id: v2.correlation_id,
+            headers: v2.headers,
+            ..Self::new(&v2.topic, v2.data)
+        }
+    }
+}
+
+// Event as serialized by legacy nodes.
+#[derive(Deserialize)]
+struct LegacyEvent {
+    topic: String,
+    timestamp: DateTime<Utc>,
+    data: Bytes,
+}
+
+impl From<LegacyEvent> for Event {
+    fn from(legacy: LegacyEvent) -> Self {
+        Self {
+            timestamp: legacy.timestamp,
+            ..Self::new(&legacy.topic, legacy.data)
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! This module offers the indexed matching engine used by the `Dispatcher` to find the `Subscription`s interested in an `Event`,
+//! without running every `Interest` against every topic.
+
+use std::collections::HashMap;
+
+use regex::{Regex, RegexSet};
+use tracing::warn;
+
+use super::{Interest, SubscriptionId};
+
+/// Separator of the levels of a hierarchical topic.
+pub const LEVEL_SEPARATOR: char = '/';
+/// Wildcard matching exactly one level of a hierarchical topic.
+pub const SINGLE_LEVEL: &str = "+";
+/// Wildcard matching all the remaining levels of a hierarchical topic, including none.
+pub const MULTI_LEVEL: &str = "#";
+
+/// Returns `true` if the `topic` matches the hierarchical wildcard `pattern`, `false` otherwise.
+pub fn wildcard_match(pattern: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split(LEVEL_SEPARATOR);
+    for level in pattern.split(LEVEL_SEPARATOR) {
+        if level == MULTI_LEVEL {
+            return true;
+        }
+        match topic_levels.next() {
+            Some(t) if level == SINGLE_LEVEL || level == t => {},
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+// Returns the topic `Interest`s used to index the given one, i.e. the ones of which at least one is satisfied by all the matching `Event`s.
+fn topic_keys(interest: &Interest) -> Option<Vec<&Interest>> {
+    match interest {
+        Interest::Exact(_) | Interest::Wildcard(_) | Interest::Regex(_) => Some(vec![interest]),
+        Interest::All(interests) => interests.iter().find_map(topic_keys),
+        Interest::Any(interests) => interests.iter().map(topic_keys).collect::<Option<Vec<_>>>().map(|keys| keys.concat()),
+        _ => None,
+    }
+}
+
+// Returns the literal topic of a regex in the form `^literal$`, if any.
+fn regex_literal(regex: &Regex) -> Option<&str> {
+    let inner = regex.as_str().strip_prefix('^')?.strip_suffix('$')?;
+    if regex::escape(inner) == inner {
+        Some(inner)
+    } else {
+        None
+    }
+}
+
+/// Index of the `Interest`s of the subscribed `Subscription`s.
+///
+/// Exact topics are looked up in a hash map, hierarchical wildcards are stored in a trie, and only the remaining
+/// regular expressions are evaluated, all at once, through a `RegexSet`.
+/// The `Interest`s that do not depend on the topic are always returned as candidates.
+#[derive(Debug, Default)]
+pub struct TopicIndex {
+    exact: HashMap<String, Vec<SubscriptionId>>,
+    trie: TrieNode,
+    regexes: Vec<(SubscriptionId, Regex)>,
+    set: Option<RegexSet>,
+    unindexed: Vec<SubscriptionId>,
+}
+
+impl TopicIndex {
+    /// Creates a new empty `TopicIndex` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the `Interest` of the `Subscription` with the given id to the index.
+    pub fn insert(&mut self, id: SubscriptionId, interest: &Interest) {
+        let keys = match topic_keys(interest) {
+            Some(keys) => keys,
+            None => return self.unindexed.push(id),
+        };
+        for key in keys {
+            match key {
+                Interest::Exact(topic) => self.exact.entry(topic.clone()).or_default().push(id),
+                Interest::Wildcard(pattern) => self.trie.insert(pattern.split(LEVEL_SEPARATOR), id),
+                Interest::Regex(regex) => {
+                    if let Some(topic) = regex_literal(regex) {
+                        self.exact.entry(topic.to_string()).or_default().push(id);
+                    } else {
+                        self.regexes.push((id, regex.clone()));
+                        self.rebuild_set();
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Removes the `Interest` of the `Subscription` with the given id from the index.
+    pub fn remove(&mut self, id: SubscriptionId, interest: &Interest) {
+        let keys = match topic_keys(interest) {
+            Some(keys) => keys,
+            None => return self.unindexed.retain(|i| *i != id),
+        };
+        for key in keys {
+            match key {
+                Interest::Exact(topic) => self.remove_exact(id, topic),
+                Interest::Wildcard(pattern) => {
+                    self.trie.remove(pattern.split(LEVEL_SEPARATOR), id);
+                },
+                Interest::Regex(regex) => {
+                    if let Some(topic) = regex_literal(regex) {
+                        self.remove_exact(id, topic);
+                    } else {
+                        self.regexes.retain(|(i, _)| *i != id);
+                        self.rebuild_set();
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Returns the ids of the `Subscription`s whose `Interest` matches the given topic.
+    /// 
+    /// The ids of the unindexed `Interest`s are included too, so they must be validated against the whole `Event`.
+    pub fn matches(&self, topic: &str) -> Vec<SubscriptionId> {
+        let mut ids = Vec::new();
+        if let Some(exact) = self.exact.get(topic) {
+            ids.extend_from_slice(exact);
+        }
+        self.trie.collect(topic.split(LEVEL_SEPARATOR), &mut ids);
+        match &self.set {
+            Some(set) => ids.extend(set.matches(topic).into_iter().map(|i| self.regexes[i].0)),
+            // The `RegexSet` could not be built, e.g. exceeding its size limit, so each regex is matched in turn.
+            None => ids.extend(self.regexes.iter().filter(|(_, re)| re.is_match(topic)).map(|(id, _)| *id)),
+        }
+        ids.extend_from_slice(&self.unindexed);
+        // The same id is found more than once if many alternatives of an `Interest` match the topic.
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    fn remove_exact(&mut self, id: SubscriptionId, topic: &str) {
+        if let Some(ids) = self.exact.get_mut(topic) {
+            ids.retain(|i| *i != id);
+            if ids.is_empty() {
+                self.exact.remove(topic);
+            }
+        }
+    }
+
+    // Rebuilds the `RegexSet` after a change of the regular expressions, leaving none if it cannot be built.
+    fn rebuild_set(&mut self) {
+        if self.regexes.is_empty() {
+            self.set = None;
+            return;
+        }
+        self.set = match RegexSet::new(self.regexes.iter().map(|(_, re)| re.as_str())) {
+            Ok(set) => Some(set),
+            Err(e) => {
+                warn!(error = %e, regexes = self.regexes.len(), "regex set not built, matching the regexes one by one");
+                None
+            },
+        };
+    }
+}
+
+// Node of the trie of hierarchical wildcards, with one level per node.
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: HashMap<String, TrieNode>,
+    single: Option<Box<TrieNode>>,
+    multi: Vec<SubscriptionId>,
+    ids: Vec<SubscriptionId>,
+}
+
+impl TrieNode {
+    fn insert<'a>(&mut self, mut levels: impl Iterator<Item = &'a str>, id: SubscriptionId) {
+        match levels.next() {
+            None => self.ids.push(id),
+            Some(MULTI_LEVEL) => self.multi.push(id),
+            Some(SINGLE_LEVEL) => self.single.get_or_insert_with(Default::default).insert(levels, id),
+            Some(level) => self.children.entry(level.to_string()).or_default().insert(levels, id),
+        }
+    }
+
+    // Returns `true` if the node is left empty.
+    fn remove<'a>(&mut self, mut levels: impl Iterator<Item = &'a str>, id: SubscriptionId) -> bool {
+        match levels.next() {
+            None => self.ids.retain(|i| *i != id),
+            Some(MULTI_LEVEL) => self.multi.retain(|i| *i != id),
+            Some(SINGLE_LEVEL) => {
+                if let Some(single) = &mut self.single {
+                    if single.remove(levels, id) {
+                        self.single = None;
+                    }
+                }
+            },
+            Some(level) => {
+                if let Some(child) = self.children.get_mut(level) {
+                    if child.remove(levels, id) {
+                        self.children.remove(level);
+                    }
+                }
+            },
+        }
+        self.is_empty()
+    }
+
+    fn collect<'a>(&self, mut levels: impl Iterator<Item = &'a str> + Clone, ids: &mut Vec<SubscriptionId>) {
+        ids.extend_from_slice(&self.multi);
+        match levels.next() {
+            None => ids.extend_from_slice(&self.ids),
+            Some(level) => {
+                if let Some(child) = self.children.get(level) {
+                    child.collect(levels.clone(), ids);
+                }
+                if let Some(single) = &self.single {
+                    single.collect(levels, ids);
+                }
+            },
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.single.is_none() && self.multi.is_empty() && self.ids.is_empty()
+    }
+}
@@ -2,25 +2,43 @@
 //! able to dispatch and forward events and messages between multiple tasks and even between multiple processes or devices,
 //! using the offered implementations of some communication protocols.
 
+pub mod error;
 pub mod framing;
 pub mod protocols;
 pub mod config;
+pub mod index;
+pub mod remote;
+pub mod request;
+#[cfg(feature = "colored")]
+pub mod logging;
+
+pub use error::Error;
+pub use request::request;
 
 #[cfg(test)]
 mod test;
 
-use std::sync::Arc;
+use std::{collections::{BTreeMap, HashMap, HashSet, VecDeque, hash_map::RandomState}, fmt::Display, hash::{BuildHasher, Hasher}, sync::{Arc, Weak, atomic::{AtomicU64, Ordering}}, time::Duration};
 use bytes::Bytes;
-use tokio::{sync::mpsc::{self, error::{TrySendError, SendError}}, select};
+use tokio::{sync::{Mutex, mpsc::{self, error::{TryRecvError, TrySendError}}}, select, time::timeout};
 use tokio_util::sync::CancellationToken;
 use regex::Regex;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use tracing::{Instrument, debug, debug_span};
+
+use framing::FrameCache;
+use index::{TopicIndex, wildcard_match};
 
 /// This struct represents the core dispatching mechanism of the system, and works using a pattern similar to
 /// publisher/subscriber.
 pub struct Dispatcher {
-    subs: Vec<Subscription>,
+    subs: HashMap<SubscriptionId, Subscription>,
+    index: TopicIndex,
+    watchers: Vec<mpsc::UnboundedSender<SubscriptionChange>>,
+    node: u64,
+    seen: HashSet<(u64, u64)>,
+    recent: VecDeque<(u64, u64)>,
     rx: mpsc::Receiver<Command>,
     token: CancellationToken,
 }
@@ -34,11 +52,17 @@ impl Dispatcher {
     /// # Returns
     /// - A tokio::sync::mpsc::Sender<`Command`> to send commands to the `Dispatcher` instance.
     /// - A tokio_util::sync::CancellationToken to handle termination.
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(buffer: usize, token: CancellationToken) -> mpsc::Sender<Command> {
         let (tx, rx) = mpsc::channel(buffer);
         let dispatcher = Self {
-            subs: Vec::default(),
-            rx: rx,
+            subs: HashMap::default(),
+            index: TopicIndex::new(),
+            watchers: Vec::new(),
+            node: random_u64(),
+            seen: HashSet::new(),
+            recent: VecDeque::new(),
+            rx,
             token: token.clone(),
         };
 
@@ -57,12 +81,25 @@ impl Dispatcher {
                 Some(cmd) = self.rx.recv() => {
                     match cmd {
                             Command::Subscribe(sub) => {
-                                println!("\x1b[93mSUB\x1b[0m [{}]", Utc::now());
-                                self.subs.push(sub);
+                                debug!(kind = "SUB", subscription = %sub.id, "subscribed");
+                                self.subscribe(sub);
+                            },
+                            Command::Unsubscribe(id) => {
+                                debug!(kind = "UNSUB", subscription = %id, "unsubscribed");
+                                self.unsubscribe(id);
                             },
-                            Command::Forward(event) => {
-                                println!("\x1b[95mPUB\x1b[0m [{}] {} - \"{}\" = {} Bytes", Utc::now(), &event.timestamp, &event.topic, event.data.len());
-                                self.dispatch(event);
+                            Command::Pause(id) => self.set_paused(id, true),
+                            Command::Resume(id) => self.set_paused(id, false),
+                            Command::SetInterest(id, interest) => self.set_interest(id, interest),
+                            Command::Watch(watcher) => self.watch(watcher),
+                            Command::Forward(mut event) => {
+                                let span = debug_span!("dispatch", topic = %event.topic);
+                                if let Some(reason) = self.admit(&mut event) {
+                                    debug!(parent: &span, topic = %event.topic, origin = ?event.origin, hops = event.hops, reason, "discarded");
+                                    continue;
+                                }
+                                debug!(parent: &span, kind = "PUB", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "published");
+                                self.dispatch(event).instrument(span).await;
                             },
                         }
                 },
@@ -70,34 +107,204 @@ impl Dispatcher {
         }
     }
 
-    // Dispatch Arc<Event> references to subscribers, while removing dead ones.
-    fn dispatch(&mut self, event: Event) {
+    // Stamps the local events with the id of this node, and returns the reason to discard the other ones, if any:
+    // exceeding their TTL, returning to this node, or already dispatched.
+    fn admit(&mut self, event: &mut Event) -> Option<&'static str> {
+        let origin = match event.origin {
+            Some(origin) => origin,
+            None => {
+                event.origin = Some(self.node);
+                return None;
+            },
+        };
+        if event.hops == 0 {
+            return None;
+        }
+        if event.hops > event.ttl {
+            return Some("expired");
+        }
+        if origin == self.node {
+            return Some("looped");
+        }
+        if !self.seen.insert((origin, event.id)) {
+            return Some("duplicate");
+        }
+        self.recent.push_back((origin, event.id));
+        if self.recent.len() > DEDUP_CAPACITY {
+            if let Some(oldest) = self.recent.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        None
+    }
+
+    // Adds a subscription to the index, while sweeping away dead ones.
+    fn subscribe(&mut self, sub: Subscription) {
+        let dead: Vec<SubscriptionId> = self.subs.values().filter(|sub| !sub.is_active()).map(|sub| sub.id).collect();
+        for id in dead {
+            self.unsubscribe(id);
+        }
+        self.index.insert(sub.id, &sub.interest);
+        if sub.advertised {
+            self.notify(SubscriptionChange::Subscribed(sub.id, sub.interest.clone()));
+        }
+        self.subs.insert(sub.id, sub);
+    }
+
+    // Removes a subscription from the index, if present.
+    fn unsubscribe(&mut self, id: SubscriptionId) {
+        if let Some(sub) = self.subs.remove(&id) {
+            self.index.remove(id, &sub.interest);
+            if sub.advertised {
+                self.notify(SubscriptionChange::Unsubscribed(id));
+            }
+        }
+    }
+
+    // Registers a watcher, notifying it of the advertised subscriptions already present.
+    fn watch(&mut self, watcher: mpsc::UnboundedSender<SubscriptionChange>) {
+        for sub in self.subs.values().filter(|sub| sub.advertised) {
+            let _ = watcher.send(SubscriptionChange::Subscribed(sub.id, sub.interest.clone()));
+        }
+        self.watchers.push(watcher);
+    }
+
+    // Notifies a change to the watchers, while removing the closed ones.
+    fn notify(&mut self, change: SubscriptionChange) {
+        self.watchers.retain(|watcher| watcher.send(change.clone()).is_ok());
+    }
+
+    // Dispatch Arc<Event> references to the candidate subscribers found by the index, while removing dead ones.
+    async fn dispatch(&mut self, event: Event) {
         let arc = Arc::new(event);
-        self.subs.retain(|sub| {
-            if sub.is_active() {
-                let _ = sub.forward(arc.clone());
-                return true;
-            } else {
-                return false
+        for id in self.index.matches(&arc.topic) {
+            let delivery = match self.subs.get(&id) {
+                Some(sub) if !sub.paused => sub.forward(arc.clone()).await,
+                _ => continue,
+            };
+            if delivery == Some(Delivery::Disconnected) {
+                self.unsubscribe(id);
             }
-        });
+        }
+    }
+
+    // Replaces the interest of the subscription with the given id, if present, and reindexes it.
+    fn set_interest(&mut self, id: SubscriptionId, interest: Interest) {
+        if let Some(sub) = self.subs.get_mut(&id) {
+            self.index.remove(id, &sub.interest);
+            self.index.insert(id, &interest);
+            sub.interest = interest;
+            if sub.advertised {
+                let change = SubscriptionChange::Subscribed(id, sub.interest.clone());
+                self.notify(change);
+            }
+        }
+    }
+
+    // Pauses or resumes the subscription with the given id, if present.
+    fn set_paused(&mut self, id: SubscriptionId, paused: bool) {
+        if let Some(sub) = self.subs.get_mut(&id) {
+            sub.paused = paused;
+        }
     }
 }
 
+/// Number of the most recent `Event`s received from other nodes remembered by the `Dispatcher` to suppress their duplicates.
+pub const DEDUP_CAPACITY: usize = 4096;
+
+/// Maximum number of links crossed by an `Event`, unless set otherwise with `Event::with_ttl()`.
+pub const DEFAULT_TTL: u8 = 16;
+
 /// Types of commands valid fo the `Dispatcher`.
 #[derive(Clone, Debug)]
 pub enum Command {
     /// Used for subscribing to the `Dispatcher`.
     Subscribe(Subscription),
+    /// Used for removing a `Subscription` from the `Dispatcher`.
+    Unsubscribe(SubscriptionId),
+    /// Used for temporarily stopping the forwarding of `Event`s to a `Subscription`.
+    Pause(SubscriptionId),
+    /// Used for restarting the forwarding of `Event`s to a paused `Subscription`.
+    Resume(SubscriptionId),
+    /// Used for replacing the `Interest` of a `Subscription`.
+    SetInterest(SubscriptionId, Interest),
     /// Used for forwarding an `Event`.
     Forward(Event),
+    /// Used for being notified of the changes of the advertised `Subscription`s, starting from the present ones.
+    Watch(mpsc::UnboundedSender<SubscriptionChange>),
+}
+
+/// Change of an advertised `Subscription`, notified to the watchers registered with `Command::Watch`.
+#[derive(Clone, Debug)]
+pub enum SubscriptionChange {
+    /// A `Subscription` has been added, or its `Interest` has been replaced.
+    Subscribed(SubscriptionId, Interest),
+    /// A `Subscription` has been removed.
+    Unsubscribed(SubscriptionId),
+}
+
+// Returns a new random number, seeded by the randomly keyed hasher of the standard library.
+pub(crate) fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+/// Unique identifier of a `Subscription`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubscriptionId(u64);
+
+impl SubscriptionId {
+    // Returns a new process-wide unique id.
+    fn next() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Display for SubscriptionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Policy applied by the `Dispatcher` when the channel of a `Subscription` is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Discards the incoming `Event`.
+    #[default]
+    DropNewest,
+    /// Discards the oldest buffered `Event` to make room for the incoming one, as a ring buffer.
+    DropOldest,
+    /// Blocks the `Dispatcher` until there is room for the incoming `Event`, or discards it once the timeout expires.
+    Block(Duration),
+    /// Discards the incoming `Event` and removes the `Subscription`, closing its channel.
+    Disconnect,
+}
+
+/// Outcome of the delivery of an `Event` to a `Subscription`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// The `Event` has been buffered in the `Subscription` channel.
+    Delivered,
+    /// An `Event` has been discarded according to the `Backpressure` policy.
+    Dropped,
+    /// The `Subscription` channel is closed, or the `Subscription` has to be removed according to the `Backpressure` policy.
+    Disconnected,
 }
 
 /// Models the subscription of a task to the `Dispatcher`.
 #[derive(Clone, Debug)]
 pub struct Subscription {
+    id: SubscriptionId,
     interest: Interest,
     tx: mpsc::Sender<Arc<Event>>,
+    rx: Weak<Mutex<mpsc::Receiver<Arc<Event>>>>,
+    policy: Backpressure,
+    dropped: Arc<AtomicU64>,
+    paused: bool,
+    advertised: bool,
 }
 
 impl Subscription {
@@ -106,16 +313,24 @@ impl Subscription {
     /// # Parameters
     /// - `interest` : represents the validation criteria according to which the `Dispatcher` forwards an `Event` to this `Subscription`.
     /// - `buffer` : indicates the size of the buffer of the incoming channel of the `Subscription`.
+    /// - `policy` : indicates how the `Dispatcher` behaves when the buffer is full.
     /// 
     /// # Returns
     /// - The `Subscription` instance.
     /// - The receiver end of the channel used by the `Dispatcher` to forward the `Event`s.
-    pub fn new(interest: Interest, buffer: usize) -> (Self, mpsc::Receiver<Arc<Event>>) {
+    pub fn new(interest: Interest, buffer: usize, policy: Backpressure) -> (Self, Inbox) {
         let (tx, rx) = mpsc::channel(buffer);
+        let rx = Arc::new(Mutex::new(rx));
         (Self {
+            id: SubscriptionId::next(),
             interest,
             tx,
-        }, rx)
+            rx: Arc::downgrade(&rx),
+            policy,
+            dropped: Arc::default(),
+            paused: false,
+            advertised: true,
+        }, Inbox { rx })
     }
 
     /// Creates a `Subscription` with the `new()` method and automatically subscribes it to the given `Dispatcher`.
@@ -123,14 +338,47 @@ impl Subscription {
     /// # Parameters
     /// - `interest` : represents the validation criteria according to which the `Dispatcher` forwards an `Event` to this `Subscription`.
     /// - `buffer` : indicates the size of the buffer of the incoming channel of the `Subscription`.
+    /// - `policy` : indicates how the `Dispatcher` behaves when the buffer is full.
     /// - `dispatcher` : represents the sender linked to the desired `Dispatcher`.
     /// 
     /// # Returns
-    /// - The receiver end of the channel used by the `Dispatcher` to forward the `Event`s, wrapped in a `Result`.
-    pub async fn subscribe(interest: Interest, buffer: usize, dispatcher: mpsc::Sender<Command>) -> Result<mpsc::Receiver<Arc<Event>>, SendError<Command>> {
-        let (sub, rx) = Self::new(interest, buffer);
+    /// - The `SubscriptionHandle` used to control the `Subscription` at runtime.
+    /// - The receiver end of the channel used by the `Dispatcher` to forward the `Event`s.
+    /// 
+    /// Both are wrapped in a `Result`.
+    pub async fn subscribe(interest: Interest, buffer: usize, policy: Backpressure, dispatcher: mpsc::Sender<Command>) -> error::Result<(SubscriptionHandle, Inbox)> {
+        let (sub, rx) = Self::new(interest, buffer, policy);
+        let handle = sub.handle(dispatcher.clone());
         dispatcher.send(Command::Subscribe(sub)).await?;
-        Ok(rx)
+        Ok((handle, rx))
+    }
+
+    /// Returns the `SubscriptionId` of the `Subscription`.
+    pub fn id(&self) -> SubscriptionId {
+        self.id
+    }
+
+    /// Sets whether the `Subscription` is advertised to the remote peers, as it is by default.
+    ///
+    /// The `Subscription`s forwarding `Event`s to other nodes should not be advertised, so that only the ones of the
+    /// local subscribers are.
+    pub fn advertised(mut self, advertised: bool) -> Self {
+        self.advertised = advertised;
+        self
+    }
+
+    /// Returns a `SubscriptionHandle` bound to the given `Dispatcher`, to control the `Subscription` once it is subscribed.
+    pub fn handle(&self, dispatcher: mpsc::Sender<Command>) -> SubscriptionHandle {
+        SubscriptionHandle {
+            id: self.id,
+            dispatcher,
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Returns the number of `Event`s discarded so far according to the `Backpressure` policy.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
     }
 
     /// Returns `true` if the `Subscription` channel is not closed, `false` otherwise.
@@ -138,37 +386,224 @@ impl Subscription {
         !self.tx.is_closed()
     }
 
-    /// Forwards the input `Arc<Event>` through the `Subscription` channel if the `Event` is valid for the `Subscription`.
-    pub fn forward(&self, event: Arc<Event>) -> Option<Result<(), TrySendError<Arc<Event>>>> {
+    /// Forwards the input `Arc<Event>` through the `Subscription` channel if the `Event` is valid for the `Subscription`,
+    /// applying the `Backpressure` policy if the channel is full.
+    pub async fn forward(&self, event: Arc<Event>) -> Option<Delivery> {
         if self.is_valid(event.as_ref()) {
-            return Some(self.tx.try_send(event));
+            return Some(self.deliver(event).await);
         }
         None
     }
 
+    // Forwards the input `Arc<Event>` through the `Subscription` channel, without validating it.
+    async fn deliver(&self, event: Arc<Event>) -> Delivery {
+        let event = match self.tx.try_send(event) {
+            Ok(()) => return Delivery::Delivered,
+            Err(TrySendError::Closed(_)) => return Delivery::Disconnected,
+            Err(TrySendError::Full(event)) => event,
+        };
+        let delivery = match self.policy {
+            Backpressure::DropNewest => Delivery::Dropped,
+            Backpressure::DropOldest => {
+                // If the receiver is locked, the subscriber is already consuming the buffer, so the newest is dropped instead.
+                if let Some(rx) = self.rx.upgrade() {
+                    if let Ok(mut rx) = rx.try_lock() {
+                        let _ = rx.try_recv();
+                    }
+                }
+                match self.tx.try_send(event) {
+                    Err(TrySendError::Closed(_)) => return Delivery::Disconnected,
+                    _ => Delivery::Dropped,
+                }
+            },
+            Backpressure::Block(duration) => {
+                match timeout(duration, self.tx.send(event)).await {
+                    Ok(Ok(())) => return Delivery::Delivered,
+                    Ok(Err(_)) => return Delivery::Disconnected,
+                    Err(_) => Delivery::Dropped,
+                }
+            },
+            Backpressure::Disconnect => Delivery::Disconnected,
+        };
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        debug!(kind = "DROP", subscription = %self.id, policy = ?self.policy, "dropped");
+        delivery
+    }
+
     /// Returns `true` is the `Event` is valid according to the `Interest` of the `Subscription`, `false` otherwise.
     pub fn is_valid(&self, event: &Event) -> bool {
         self.interest.is_valid(event)
     }
 }
 
+/// Allows to cancel, pause and resume a `Subscription` without dropping its receiver.
+#[derive(Clone, Debug)]
+pub struct SubscriptionHandle {
+    id: SubscriptionId,
+    dispatcher: mpsc::Sender<Command>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl SubscriptionHandle {
+    /// Returns the `SubscriptionId` of the controlled `Subscription`.
+    pub fn id(&self) -> SubscriptionId {
+        self.id
+    }
+
+    /// Removes the `Subscription` from the `Dispatcher`, closing its channel.
+    pub async fn cancel(&self) -> error::Result<()> {
+        Ok(self.dispatcher.send(Command::Unsubscribe(self.id)).await?)
+    }
+
+    /// Stops the forwarding of `Event`s to the `Subscription`, until `resume()` is called.
+    /// 
+    /// `Event`s dispatched while paused are not buffered.
+    pub async fn pause(&self) -> error::Result<()> {
+        Ok(self.dispatcher.send(Command::Pause(self.id)).await?)
+    }
+
+    /// Restarts the forwarding of `Event`s to a paused `Subscription`.
+    pub async fn resume(&self) -> error::Result<()> {
+        Ok(self.dispatcher.send(Command::Resume(self.id)).await?)
+    }
+
+    /// Replaces the `Interest` of the `Subscription`, which keeps its channel and its buffered `Event`s.
+    pub async fn set_interest(&self, interest: Interest) -> error::Result<()> {
+        Ok(self.dispatcher.send(Command::SetInterest(self.id, interest)).await?)
+    }
+
+    /// Returns the number of `Event`s discarded so far according to the `Backpressure` policy of the `Subscription`.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Receiver end of the channel of a `Subscription`.
+#[derive(Debug)]
+pub struct Inbox {
+    rx: Arc<Mutex<mpsc::Receiver<Arc<Event>>>>,
+}
+
+impl Inbox {
+    /// Receives the next `Event`, or `None` if the `Subscription` has been removed and the buffer is empty.
+    pub async fn recv(&mut self) -> Option<Arc<Event>> {
+        self.rx.lock().await.recv().await
+    }
+
+    /// Tries to receive the next `Event` without waiting.
+    pub fn try_recv(&mut self) -> Result<Arc<Event>, TryRecvError> {
+        match self.rx.try_lock() {
+            Ok(mut rx) => rx.try_recv(),
+            Err(_) => Err(TryRecvError::Empty),
+        }
+    }
+}
+
 /// Represents the interest of a `Subscription` in a certain class of `Event`s.
 #[derive(Clone, Debug)]
-pub struct Interest {
-    validator: Regex,
+pub enum Interest {
+    /// Matches the `Event`s whose topic is equal to the given one.
+    Exact(String),
+    /// Matches the `Event`s whose hierarchical topic (e.g. `a/b/c`) matches the given pattern,
+    /// where `+` matches exactly one level and a trailing `#` matches all the remaining ones (e.g. `a/+/#`).
+    Wildcard(String),
+    /// Matches the `Event`s whose topic matches the regex pattern.
+    Regex(Regex),
+    /// Matches the `Event`s carrying the given header, with a value matching the regex pattern.
+    Header(String, Regex),
+    /// Matches the `Event`s whose data size, in bytes, is within the given bounds, both inclusive.
+    Size {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    /// Matches the `Event`s whose timestamp is within the given bounds, both inclusive.
+    Time {
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    },
+    /// Matches the `Event`s received from a node whose address matches the regex pattern (see `SOURCE_HEADER`).
+    Source(Regex),
+    /// Matches the `Event`s carrying the given `correlation_id`, e.g. the replies to a request.
+    Correlation(u64),
+    /// Matches the `Event`s matched by all the given `Interest`s.
+    All(Vec<Interest>),
+    /// Matches the `Event`s matched by at least one of the given `Interest`s.
+    Any(Vec<Interest>),
+    /// Matches the `Event`s not matched by the given `Interest`.
+    Not(Box<Interest>),
 }
 
 impl Interest {
     /// Creates a new `Interest` instance using a `Regex` as validator.
     pub fn new(validator: Regex) -> Self {
-        Self {
-            validator,
+        Self::Regex(validator)
+    }
+
+    /// Creates a new `Interest` instance matching only the given topic.
+    pub fn exact(topic: &str) -> Self {
+        Self::Exact(String::from(topic))
+    }
+
+    /// Creates a new `Interest` instance matching the given hierarchical wildcard pattern.
+    pub fn wildcard(pattern: &str) -> Self {
+        Self::Wildcard(String::from(pattern))
+    }
+
+    /// Creates a new `Interest` instance matching only the `Event`s matched by both `self` and `other`.
+    pub fn and(self, other: Interest) -> Self {
+        match self {
+            Self::All(mut interests) => {
+                interests.push(other);
+                Self::All(interests)
+            },
+            interest => Self::All(vec![interest, other]),
         }
     }
 
-    /// Returns `true` if the `event.topic` matches the regex pattern.
+    /// Creates a new `Interest` instance matching the `Event`s matched by either `self` or `other`.
+    pub fn or(self, other: Interest) -> Self {
+        match self {
+            Self::Any(mut interests) => {
+                interests.push(other);
+                Self::Any(interests)
+            },
+            interest => Self::Any(vec![interest, other]),
+        }
+    }
+
+    /// Returns `true` if the `Event` matches the `Interest`, `false` otherwise.
     pub fn is_valid(&self, event: &Event) -> bool {
-        self.validator.is_match(&event.topic)
+        match self {
+            Self::Exact(topic) => topic == &event.topic,
+            Self::Wildcard(pattern) => wildcard_match(pattern, &event.topic),
+            Self::Regex(validator) => validator.is_match(&event.topic),
+            Self::Header(name, validator) => {
+                event.header(name).and_then(HeaderValue::as_str).is_some_and(|value| validator.is_match(value))
+            },
+            Self::Size { min, max } => {
+                let size = event.data.len();
+                min.is_none_or(|min| size >= min) && max.is_none_or(|max| size <= max)
+            },
+            Self::Time { after, before } => {
+                after.is_none_or(|after| event.timestamp >= after) && before.is_none_or(|before| event.timestamp <= before)
+            },
+            Self::Source(validator) => {
+                event.header(SOURCE_HEADER).and_then(HeaderValue::as_str).is_some_and(|value| validator.is_match(value))
+            },
+            Self::Correlation(id) => event.correlation_id == Some(*id),
+            Self::All(interests) => interests.iter().all(|interest| interest.is_valid(event)),
+            Self::Any(interests) => interests.iter().any(|interest| interest.is_valid(event)),
+            Self::Not(interest) => !interest.is_valid(event),
+        }
+    }
+}
+
+impl std::ops::Not for Interest {
+    type Output = Self;
+
+    /// Creates a new `Interest` instance matching the `Event`s not matched by `self`.
+    fn not(self) -> Self {
+        Self::Not(Box::new(self))
     }
 }
 
@@ -181,6 +616,32 @@ pub struct Event {
     pub timestamp: DateTime<Utc>,
     /// Contains the raw data of the `Event`.
     pub data: Bytes,
+    /// Identifies the request this `Event` belongs to, if it is part of a request/reply exchange.
+    #[serde(default)]
+    pub correlation_id: Option<u64>,
+    /// Contains the metadata of the `Event`.
+    #[serde(default)]
+    pub headers: Headers,
+    /// Identifies the `Event` among the ones of its `origin`, to suppress its duplicates.
+    #[serde(default = "random_u64")]
+    pub id: u64,
+    /// Identifies the node the `Event` was first dispatched by, set by its `Dispatcher`.
+    #[serde(default)]
+    pub origin: Option<u64>,
+    /// Number of links crossed so far by the `Event`, increased by each node receiving it.
+    #[serde(default)]
+    pub hops: u8,
+    /// Maximum number of links the `Event` can cross, after which it is discarded.
+    #[serde(default = "default_ttl")]
+    pub ttl: u8,
+    // Frame of the `Event` once serialized behind an `Arc`, shared by all the peers it is fanned out to.
+    #[serde(skip)]
+    frame: FrameCache,
+}
+
+// Returns the TTL of the `Event`s whose serialization does not contain it.
+fn default_ttl() -> u8 {
+    DEFAULT_TTL
 }
 
 impl Event {
@@ -190,6 +651,94 @@ impl Event {
             topic: String::from(topic),
             timestamp: Utc::now(),
             data,
+            correlation_id: None,
+            headers: Headers::new(),
+            id: random_u64(),
+            origin: None,
+            hops: 0,
+            ttl: DEFAULT_TTL,
+            frame: FrameCache::default(),
+        }
+    }
+
+    /// Returns the `Event` with the given maximum number of links it can cross.
+    pub fn with_ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Returns the `Event` with the given header added, replacing any previous value.
+    pub fn with_header<V: Into<HeaderValue>>(mut self, name: &str, value: V) -> Self {
+        self.headers.insert(String::from(name), value.into());
+        self
+    }
+
+    /// Returns the value of the given header, if present.
+    pub fn header(&self, name: &str) -> Option<&HeaderValue> {
+        self.headers.get(name)
+    }
+
+    /// Returns the time elapsed since the creation of the `Event`.
+    pub fn age(&self) -> chrono::Duration {
+        Utc::now() - self.timestamp
+    }
+
+    /// Creates a new `Event` instance replying to this one, i.e. carrying the same `correlation_id`.
+    pub fn reply(&self, topic: &str, data: Bytes) -> Self {
+        Self {
+            correlation_id: self.correlation_id,
+            ..Self::new(topic, data)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Header set by the receivers to the address of the node an `Event` was received from.
+pub const SOURCE_HEADER: &str = "commnode.source";
+
+/// Map of the headers of an `Event`, sorted by name.
+pub type Headers = BTreeMap<String, HeaderValue>;
+
+/// Value of a header of an `Event`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum HeaderValue {
+    /// Textual value.
+    Text(String),
+    /// Raw value.
+    Bytes(Bytes),
+}
+
+impl HeaderValue {
+    /// Returns the value as a string slice, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            Self::Bytes(bytes) => std::str::from_utf8(bytes).ok(),
+        }
+    }
+
+    /// Returns the value as a byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Text(text) => text.as_bytes(),
+            Self::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+impl From<&str> for HeaderValue {
+    fn from(value: &str) -> Self {
+        Self::Text(String::from(value))
+    }
+}
+
+impl From<String> for HeaderValue {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<Bytes> for HeaderValue {
+    fn from(value: Bytes) -> Self {
+        Self::Bytes(value)
+    }
+}
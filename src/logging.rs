@@ -0,0 +1,64 @@
+//! This module offers a `tracing` formatter reproducing the colored traffic logs of the previous versions,
+//! i.e. one `SUB`/`PUB`/`IN`/`OUT` line for every subscription, dispatched, received and sent `Event`.
+
+use std::{collections::HashMap, fmt};
+
+use chrono::Utc;
+use tracing::{Level, Subscriber, field::{Field, Visit}};
+use tracing_subscriber::{fmt::{FmtContext, FormatEvent, FormatFields, format::Writer}, registry::LookupSpan};
+
+/// Formats the traffic events of the crate as colored lines, and any other event as `LEVEL target: fields`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ColoredFormatter;
+
+impl<S, N> FormatEvent<S, N> for ColoredFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &tracing::Event<'_>) -> fmt::Result {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let field = |name: &str| fields.0.get(name).map(String::as_str).unwrap_or_default();
+        let now = Utc::now();
+        match field("kind") {
+            kind @ ("SUB" | "UNSUB" | "DROP") => writeln!(writer, "\x1b[93m{}\x1b[0m [{}] #{}", kind, now, field("subscription")),
+            kind @ ("PUB" | "IN" | "OUT") => {
+                let color = match kind {
+                    "PUB" => "\x1b[95m",
+                    "IN" => "\x1b[96m",
+                    _ => "\x1b[94m",
+                };
+                writeln!(writer, "{}{}\x1b[0m [{}] {} - \"{}\" = {} Bytes", color, kind, now, field("timestamp"), field("topic"), field("size"))
+            },
+            _ => {
+                let metadata = event.metadata();
+                write!(writer, "{} {}: ", metadata.level(), metadata.target())?;
+                ctx.field_format().format_fields(writer.by_ref(), event)?;
+                writeln!(writer)
+            },
+        }
+    }
+}
+
+/// Installs a global `tracing` subscriber printing the events up to the given level with the `ColoredFormatter`.
+pub fn init(level: Level) {
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .event_format(ColoredFormatter)
+        .init();
+}
+
+// Collects the fields of an event as strings.
+#[derive(Default)]
+struct Fields(HashMap<&'static str, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name(), format!("{:?}", value));
+    }
+}
@@ -0,0 +1,50 @@
+//! This module offers the detection of dead peers on the connections of the `TCP`, `TLS`, `UNIX` and `UDP` protocols.
+//!
+//! Senders transmit a heartbeat control `Event` whenever they have been idle for `HEARTBEAT_INTERVAL`, and receivers
+//! configured with an idle timeout consider a peer lost once nothing is received from it for that long, e.g. because
+//! it was power-cycled or its network dropped without closing the connection.
+//! The lost connections are notified as local `Event`s on `CONNECTION_LOST_TOPIC`, whose data is the address of the peer.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::time::{Instant, sleep_until};
+
+use crate::{Event, SOURCE_HEADER};
+
+/// Time of inactivity of a sender after which it transmits a heartbeat. The idle timeouts should be a few times longer.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// Topic of the heartbeat control `Event`s, which are never dispatched.
+pub const HEARTBEAT_TOPIC: &str = "$heartbeat";
+/// Reserved system topic of the local `Event`s notifying a lost connection.
+pub const CONNECTION_LOST_TOPIC: &str = "$system/connection_lost";
+
+/// Builds a heartbeat control `Event`.
+pub fn heartbeat_event() -> Event {
+    Event::new(HEARTBEAT_TOPIC, Bytes::new()).with_ttl(0)
+}
+
+/// Returns `true` if the `Event` is a heartbeat, `false` otherwise.
+pub fn is_heartbeat(event: &Event) -> bool {
+    event.topic == HEARTBEAT_TOPIC
+}
+
+/// Builds the local `Event` notifying that the connection with the given peer is lost, which is never forwarded to other nodes.
+pub fn connection_lost_event(peer: &str) -> Event {
+    Event::new(CONNECTION_LOST_TOPIC, Bytes::from(peer.to_string()))
+        .with_header(SOURCE_HEADER, peer)
+        .with_ttl(0)
+}
+
+/// Returns `true` if the `Event` is a local notification on a reserved system topic, `false` otherwise.
+pub fn is_system(event: &Event) -> bool {
+    event.topic.starts_with("$system/")
+}
+
+// Waits until the peer heard at the given instant is silent for longer than the timeout, or forever if there is none.
+pub(crate) async fn expired(last: Instant, timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => sleep_until(last + timeout).await,
+        None => std::future::pending().await,
+    }
+}
@@ -4,13 +4,35 @@ use std::fmt::Display;
 
 use serde::{Serialize, Deserialize};
 
+pub mod heartbeat;
+pub mod quic;
 pub mod tcp;
+pub mod tls;
+pub mod transport;
 pub mod udp;
+#[cfg(unix)]
+pub mod unix;
+pub mod ws;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Protocol of a channel, either a built-in one or a `Transport` registered by name in the `Registry`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Protocol {
     TCP,
     UDP,
+    /// UDP without delivery guarantees, sending each `Event` as a single datagram.
+    DATAGRAM,
+    /// TCP secured by TLS, configured by the `TlsConfig` of the channel.
+    TLS,
+    /// Unix domain sockets, addressed by the path of the socket. Available only on Unix platforms.
+    UNIX,
+    /// WebSocket, whose encoding is configured by the `WsMode` of the channel.
+    /// A sender connects to the server at a `ws://` address, or else serves its `Event`s to the clients connecting to the address.
+    WS,
+    /// QUIC, secured by the `TlsConfig` of the channel, with a stream for each topic and for each large `Event`.
+    QUIC,
+    /// `Transport` registered with the given name in the `Registry` passed to `init_connections()`.
+    #[serde(untagged)]
+    Custom(String),
 }
 
 impl Display for Protocol {
@@ -18,6 +40,12 @@ impl Display for Protocol {
         let string = match self {
             Self::TCP => "TCP",
             Self::UDP => "UDP",
+            Self::DATAGRAM => "DATAGRAM",
+            Self::TLS => "TLS",
+            Self::UNIX => "UNIX",
+            Self::WS => "WS",
+            Self::QUIC => "QUIC",
+            Self::Custom(name) => name,
         };
         write!(f, "{}", string)
     }
@@ -0,0 +1,227 @@
+//! This module offers functions to use the QUIC protocol, through `quinn`, for sending and receiving `Event`s.
+//!
+//! A sender writes the `Event`s of each topic on a dedicated unidirectional stream, and each `Event` whose frame exceeds
+//! `STREAM_THRESHOLD` on a stream of its own, so that large `Event`s do not block the small ones behind them.
+//! Hence the `Event`s are delivered in order only within the same topic, excluding the large ones.
+//! The stream of a topic is finished once idle for `STREAM_IDLE`, or to make room for a new topic, and the streams open
+//! at once are at most `MAX_STREAMS`, so that the sender waits for the peer rather than buffering without bounds.
+//! The connections are secured by TLS, configured by the same `TlsConfig` of the `TLS` protocol.
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::{Duration, Instant}};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use quinn::{ClientConfig, Connection, ConnectionError, Endpoint, RecvStream, ServerConfig, TransportConfig, VarInt};
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use tokio::net::ToSocketAddrs;
+use tokio::select;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, mpsc::{self, error::SendError}};
+use tokio::time::timeout;
+use tokio_util::codec::{FramedRead, FramedWrite};
+use tokio_util::sync::CancellationToken;
+
+use tracing::{Instrument, debug, info_span, warn};
+
+use super::udp::resolve;
+use crate::framing::{decode, encode_shared, length_codec};
+use crate::{Error, Event, HeaderValue, SOURCE_HEADER, error::Result};
+
+/// Size, in bytes, of the frames of the `Event`s sent on a stream of their own.
+pub const STREAM_THRESHOLD: usize = 64 * 1024;
+/// Maximum number of concurrent streams accepted by a receiver, and opened by a sender, for each connection.
+pub const MAX_STREAMS: u32 = 1024;
+/// Time without new `Event`s after which the stream of a topic is finished.
+pub const STREAM_IDLE: Duration = Duration::from_secs(5);
+
+// Number of frames buffered for the stream of each topic.
+const TOPIC_BUFFER: usize = 32;
+// Maximum number of topics with an open stream, leaving the other streams to the large `Event`s.
+const TOPIC_STREAMS: usize = MAX_STREAMS as usize / 2;
+
+/// Runs a new task acting as a QUIC listener on a given socket.
+///
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `config` : the TLS configuration of the listener, e.g. built by `TlsConfig::server_config()`.
+/// - `tx` : a transmitter to send back the `Event`s received from the QUIC connections.
+///
+/// # Returns
+/// - cancellation token for handling termination.
+pub async fn new_receiver<T: ToSocketAddrs>(addr: T, config: Arc<rustls::ServerConfig>, tx: mpsc::Sender<Event>, token: CancellationToken) -> Result<()> {
+    let crypto = QuicServerConfig::try_from(config).map_err(|e| Error::Tls(rustls::Error::General(e.to_string())))?;
+    let mut transport = TransportConfig::default();
+    transport.max_concurrent_uni_streams(VarInt::from_u32(MAX_STREAMS));
+    let mut server_config = ServerConfig::with_crypto(Arc::new(crypto));
+    server_config.transport_config(Arc::new(transport));
+    let endpoint = Endpoint::server(server_config, resolve(addr).await?).map_err(Error::Bind)?;
+    tokio::spawn(async move {
+        listen(endpoint, tx, token).await;
+    });
+    Ok(())
+}
+
+// Listener task
+async fn listen(endpoint: Endpoint, tx: mpsc::Sender<Event>, token: CancellationToken) {
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            Some(incoming) = endpoint.accept() => {
+                let peer = incoming.remote_address();
+                let clone = tx.clone();
+                let child = token.child_token();
+                tokio::spawn(async move {
+                    match incoming.await {
+                        Ok(connection) => process(connection, peer, clone, child).await,
+                        Err(e) => warn!(error = %e, "handshake failed"),
+                    }
+                }.instrument(info_span!("connection", protocol = "QUIC", %peer)));
+            },
+        }
+    }
+    endpoint.close(VarInt::from_u32(0), b"closed");
+}
+
+// Connection handler, reading every incoming stream on its own task.
+async fn process(connection: Connection, peer: SocketAddr, tx: mpsc::Sender<Event>, token: CancellationToken) {
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            stream = connection.accept_uni() => {
+                match stream {
+                    Ok(stream) => {
+                        tokio::spawn(read_stream(stream, peer, tx.clone()).in_current_span());
+                    },
+                    Err(ConnectionError::ApplicationClosed(_) | ConnectionError::LocallyClosed) => break,
+                    Err(e) => {
+                        warn!(error = %e, "connection lost");
+                        break;
+                    },
+                }
+            },
+        }
+    }
+}
+
+// Stream handler
+async fn read_stream(stream: RecvStream, peer: SocketAddr, tx: mpsc::Sender<Event>) {
+    let mut frames = FramedRead::new(stream, length_codec());
+    while let Some(frame) = frames.next().await {
+        match frame.and_then(|frame| decode(&frame)) {
+            Ok(mut event) => {
+                event.headers.insert(String::from(SOURCE_HEADER), HeaderValue::from(peer.to_string()));
+                debug!(kind = "IN", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "received");
+                let _ = tx.send(event).await;
+            },
+            Err(e) => warn!(error = %e, "invalid frame"),
+        }
+    }
+}
+
+/// Runs a new task acting as a QUIC sender to a given socket.
+///
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `server_name` : the name verified against the certificate of the listener.
+/// - `config` : the TLS configuration of the sender, e.g. built by `TlsConfig::client_config()`.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward to the QUIC connection.
+pub async fn new_sender<T: ToSocketAddrs>(addr: T, server_name: &str, config: Arc<rustls::ClientConfig>, rx: mpsc::Receiver<Arc<Event>>) -> Result<()> {
+    let crypto = QuicClientConfig::try_from(config).map_err(|e| Error::Tls(rustls::Error::General(e.to_string())))?;
+    let peer = resolve(addr).await?;
+    let local: SocketAddr = if peer.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+    let mut endpoint = Endpoint::client(local).map_err(Error::Connect)?;
+    endpoint.set_default_client_config(ClientConfig::new(Arc::new(crypto)));
+    let connecting = endpoint.connect(peer, server_name).map_err(|e| Error::Connect(std::io::Error::other(e)))?;
+    let connection = connecting.await.map_err(|e| Error::Connect(e.into()))?;
+    tokio::spawn(async move {
+        send(connection, rx).await;
+    }.instrument(info_span!("connection", protocol = "QUIC", %peer)));
+    Ok(())
+}
+
+// Sender task, distributing the frames among the streams.
+async fn send(connection: Connection, mut rx: mpsc::Receiver<Arc<Event>>) {
+    let streams = Arc::new(Semaphore::new(MAX_STREAMS as usize));
+    let mut topics: HashMap<String, (mpsc::Sender<Bytes>, Instant)> = HashMap::new();
+    loop {
+        let event = select! {
+            e = connection.closed() => {
+                warn!(error = %e, "connection lost");
+                break;
+            },
+            event = rx.recv() => match event {
+                Some(event) => event,
+                None => break,
+            },
+        };
+        debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+        let frame = match encode_shared(&event) {
+            Ok(frame) => frame,
+            Err(e) => {
+                warn!(error = %e, "send failed");
+                continue;
+            },
+        };
+        if frame.len() > STREAM_THRESHOLD {
+            let Some(stream) = open_stream(&connection, &streams, 1).await else { break };
+            let _ = stream.try_send(frame);
+            continue;
+        }
+        let frame = match topics.get_mut(&event.topic) {
+            Some((stream, used)) => {
+                *used = Instant::now();
+                match stream.send(frame).await {
+                    Ok(()) => continue,
+                    Err(SendError(frame)) => frame,
+                }
+            },
+            None => frame,
+        };
+        // The topic has no stream yet, or its stream was finished while idle.
+        topics.remove(&event.topic);
+        if topics.len() >= TOPIC_STREAMS {
+            if let Some(oldest) = topics.iter().min_by_key(|(_, (_, used))| *used).map(|(topic, _)| topic.clone()) {
+                topics.remove(&oldest);
+            }
+        }
+        let Some(stream) = open_stream(&connection, &streams, TOPIC_BUFFER).await else { break };
+        if stream.send(frame).await.is_err() {
+            warn!(topic = %event.topic, "send failed");
+            continue;
+        }
+        topics.insert(event.topic.clone(), (stream, Instant::now()));
+    }
+}
+
+// Opens a new stream once fewer than `MAX_STREAMS` are open, returning the transmitter of its frames.
+async fn open_stream(connection: &Connection, streams: &Arc<Semaphore>, buffer: usize) -> Option<mpsc::Sender<Bytes>> {
+    let permit = streams.clone().acquire_owned().await.ok()?;
+    let (tx, rx) = mpsc::channel(buffer);
+    tokio::spawn(write_stream(connection.clone(), rx, permit).in_current_span());
+    Some(tx)
+}
+
+// Writes the frames on a new stream, which is finished once they are all acknowledged by the peer, after the
+// transmitter is dropped or idle for `STREAM_IDLE`, releasing its permit.
+async fn write_stream(connection: Connection, mut rx: mpsc::Receiver<Bytes>, _permit: OwnedSemaphorePermit) {
+    let stream = match connection.open_uni().await {
+        Ok(stream) => stream,
+        Err(e) => return warn!(error = %e, "send failed"),
+    };
+    let mut frames = FramedWrite::new(stream, length_codec());
+    loop {
+        match timeout(STREAM_IDLE, rx.recv()).await {
+            Ok(Some(frame)) => {
+                if let Err(e) = frames.send(frame).await {
+                    return warn!(error = %e, "send failed");
+                }
+            },
+            Ok(None) => break,
+            // The frames sent meanwhile are still written, then the sender opens a new stream for the next ones.
+            Err(_) => rx.close(),
+        }
+    }
+    let mut stream = frames.into_inner();
+    if stream.finish().is_ok() {
+        let _ = stream.stopped().await;
+    }
+}
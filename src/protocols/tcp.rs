@@ -1,61 +1,165 @@
 //! This module offers functions to use the TCP communication protocol for sending and receiving `Event`s.
 
+use std::{collections::VecDeque, fmt::Display, future::Future, io, net::SocketAddr, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 use tokio::select;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{Instant, sleep, sleep_until};
 use tokio_util::sync::CancellationToken;
 
-use futures::{StreamExt, SinkExt};
+use futures::{StreamExt, SinkExt, future::BoxFuture};
+
+use tracing::{Instrument, debug, info_span, warn};
 
-use chrono::Utc;
+use super::heartbeat::{HEARTBEAT_INTERVAL, connection_lost_event, expired, heartbeat_event, is_heartbeat};
+use super::transport::{Connection, Listener, Transport};
+use crate::remote;
+use crate::config::{Channel, InterestSpec};
+use crate::framing::{EventCodec, FramedStream, FramingConfig, accept_compression, frame_stream_with, is_negotiation, is_too_large};
+use crate::{Backpressure, Command, Error, Event, HeaderValue, Inbox, Interest, SOURCE_HEADER, Subscription, SubscriptionHandle, error::Result, random_u64};
 
-use crate::framing::{FramedStream, frame_stream};
-use crate::Event;
+/// Topic of the `Event` through which a duplex sender advertises, as a JSON `InterestSpec`, the interest of the
+/// `Event`s to push back to it.
+pub const DUPLEX_TOPIC: &str = "$duplex";
 
 /// Runs a new task acting as a listener on a given socket.
 /// 
 /// # Parameters
 /// - `addr` : the socket address of the listener.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `tx` : a transmitter to send back the `Event`s received from the TCP streams.
+/// - `idle_timeout` : the time without receiving anything from a peer after which it is considered lost, if any.
+/// 
+/// # Returns
+/// - cancellation token for handling termination.
+pub async fn new_receiver<T: ToSocketAddrs>(addr: T, framing: FramingConfig, tx: mpsc::Sender<Event>, idle_timeout: Option<Duration>, token: CancellationToken) -> Result<()> {
+    let listener = TcpListener::bind(addr).await.map_err(Error::Bind)?;
+    tokio::spawn(async move {
+        listen(listener, framing, tx, None, idle_timeout, token).await;
+    });
+    Ok(())
+}
+
+/// Runs a new task acting as a TCP listener on a given socket, which also pushes back to each peer the dispatched
+/// `Event`s matching the interest it advertises with `DUPLEX_TOPIC`, on the same connection.
+/// 
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `allowed` : the `Interest` that the `Event`s pushed back must match, besides the advertised one.
+/// - `tx` : a transmitter to send back the `Event`s received from the TCP streams.
+/// - `dispatcher` : the `Dispatcher` to which the peers are subscribed.
+/// - `buffer` : the number of `Event`s buffered for each peer.
+/// - `idle_timeout` : the time without receiving anything from a peer after which it is considered lost, if any.
+/// 
+/// # Returns
+/// - cancellation token for handling termination.
+#[allow(clippy::too_many_arguments)]
+pub async fn new_duplex_receiver<T: ToSocketAddrs>(addr: T, framing: FramingConfig, allowed: Interest, tx: mpsc::Sender<Event>, dispatcher: mpsc::Sender<Command>, buffer: usize, idle_timeout: Option<Duration>, token: CancellationToken) -> Result<()> {
+    let listener = TcpListener::bind(addr).await.map_err(Error::Bind)?;
+    let duplex = Duplex { allowed, dispatcher, buffer, propagate: false };
+    tokio::spawn(async move {
+        listen(listener, framing, tx, Some(duplex), idle_timeout, token).await;
+    });
+    Ok(())
+}
+
+/// Runs a new task acting as a TCP listener on a given socket, which links with each peer to exchange the interests
+/// of their local `Subscription`s, as described in the `remote` module.
+/// 
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `allowed` : the `Interest` that the `Event`s sent to the peers must match, besides the advertised ones.
 /// - `tx` : a transmitter to send back the `Event`s received from the TCP streams.
+/// - `dispatcher` : the `Dispatcher` whose `Subscription`s are advertised, and to which the peers are subscribed.
+/// - `buffer` : the number of `Event`s buffered for each peer.
+/// - `idle_timeout` : the time without receiving anything from a peer after which it is considered lost, if any.
 /// 
 /// # Returns
 /// - cancellation token for handling termination.
-pub async fn new_receiver<T: ToSocketAddrs>(addr: T, tx: mpsc::Sender<Event>, token: CancellationToken) -> Result<(), tokio::io::Error> {
-    let listener = TcpListener::bind(addr).await?;
+#[allow(clippy::too_many_arguments)]
+pub async fn new_link_receiver<T: ToSocketAddrs>(addr: T, framing: FramingConfig, allowed: Interest, tx: mpsc::Sender<Event>, dispatcher: mpsc::Sender<Command>, buffer: usize, idle_timeout: Option<Duration>, token: CancellationToken) -> Result<()> {
+    let listener = TcpListener::bind(addr).await.map_err(Error::Bind)?;
+    let duplex = Duplex { allowed, dispatcher, buffer, propagate: true };
     tokio::spawn(async move {
-        listen(listener, tx, token).await;
+        listen(listener, framing, tx, Some(duplex), idle_timeout, token).await;
     });
     Ok(())
 }
 
+// Settings of a listener pushing `Event`s back to its peers, either as duplex or as link.
+#[derive(Clone)]
+struct Duplex {
+    allowed: Interest,
+    dispatcher: mpsc::Sender<Command>,
+    buffer: usize,
+    propagate: bool,
+}
+
 // Listener task
-async fn listen(listener: TcpListener, tx: mpsc::Sender<Event>, token: CancellationToken) {
+async fn listen(listener: TcpListener, framing: FramingConfig, tx: mpsc::Sender<Event>, duplex: Option<Duplex>, idle_timeout: Option<Duration>, token: CancellationToken) {
     loop {
         select! {
             _ = token.cancelled() => break,
-            Ok((stream, _)) = listener.accept() => {
-                let stream = frame_stream(stream);
+            Ok((stream, peer)) = listener.accept() => {
+                let codec = EventCodec::new(framing);
+                let stream = frame_stream_with(stream, codec.clone());
                 let clone = tx.clone();
                 let child = token.child_token();
+                let duplex = duplex.clone();
                 tokio::spawn(async move {
-                    process(stream, clone, child).await;
-                });
+                    match duplex {
+                        Some(duplex) if duplex.propagate => remote::link(stream, codec, peer.to_string(), duplex.allowed, clone, duplex.dispatcher, duplex.buffer, idle_timeout, child).await,
+                        Some(duplex) => process_duplex(stream, peer, clone, duplex, idle_timeout, child).await,
+                        None => process(stream, peer, clone, idle_timeout, child).await,
+                    }
+                }.instrument(info_span!("connection", protocol = "TCP", %peer)));
             },
         }
     }
 }
 
-// Stream handler
-async fn process(mut stream: FramedStream<TcpStream>, tx: mpsc::Sender<Event>, token: CancellationToken) {
+// Stream handler, shared by the protocols running on top of TCP, which notifies the peer as lost once silent for the idle timeout.
+pub(crate) async fn process<S: AsyncRead + AsyncWrite + Unpin>(mut stream: FramedStream<S>, peer: impl Display, tx: mpsc::Sender<Event>, idle_timeout: Option<Duration>, token: CancellationToken) {
+    let mut last = Instant::now();
     loop {
         select! {
             _ = token.cancelled() => break,
-        Some(msg) = stream.next() => {
-            if let Ok(event) = msg {
-                println!("\x1b[96mIN\x1b[0m [{}] {} - \"{}\" = {} Bytes", Utc::now(), &event.timestamp, &event.topic, event.data.len());
-                let _ = tx.send(event).await;
-            }
-        },
+            _ = expired(last, idle_timeout) => {
+                warn!("connection lost");
+                let _ = tx.send(connection_lost_event(&peer.to_string())).await;
+                break;
+            },
+            msg = stream.next() => {
+                last = Instant::now();
+                match msg {
+                    Some(Ok(event)) if is_heartbeat(&event) => {},
+                    Some(Ok(event)) if is_negotiation(&event) => {
+                        if let Some(reply) = accept_compression(&event) {
+                            if let Err(e) = stream.send(reply.into()).await {
+                                warn!(error = %e, "connection lost");
+                                break;
+                            }
+                        }
+                    },
+                    Some(Ok(mut event)) => {
+                        event.headers.insert(String::from(SOURCE_HEADER), HeaderValue::from(peer.to_string()));
+                        debug!(kind = "IN", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "received");
+                        let _ = tx.send(event).await;
+                    },
+                    Some(Err(e)) if is_too_large(&e) => {
+                        warn!(error = %e, "frame rejected");
+                        break;
+                    },
+                    Some(Err(e)) => warn!(error = %e, "invalid frame"),
+                    None => break,
+                }
+            },
         }
     }
 }
@@ -64,20 +168,515 @@ async fn process(mut stream: FramedStream<TcpStream>, tx: mpsc::Sender<Event>, t
 /// 
 /// # Parameters
 /// - `addr` : the socket address of the listener.
+/// - `framing` : the serialization format and the compression of the frames.
 /// - `rx` : a receiver to use as the source of the `Event`s to forward to the TCP stream.
-pub async fn new_sender<T: ToSocketAddrs>(addr: T, rx: mpsc::Receiver<Event>) -> Result<(), Box<dyn std::error::Error>> {
-    let stream = TcpStream::connect(addr).await?;
-    let stream = frame_stream(stream);
+pub async fn new_sender<T: ToSocketAddrs>(addr: T, framing: FramingConfig, rx: mpsc::Receiver<Arc<Event>>) -> Result<()> {
+    let stream = TcpStream::connect(addr).await.map_err(Error::Connect)?;
+    let peer = stream.peer_addr().map_err(Error::Connect)?;
+    let codec = EventCodec::new(framing);
+    let stream = frame_stream_with(stream, codec.clone());
     tokio::spawn(async move {
-        send(stream, rx).await;
-    });
+        send(stream, codec, rx).await;
+    }.instrument(info_span!("connection", protocol = "TCP", %peer)));
+    Ok(())
+}
+
+// Sender task, shared by the protocols running on top of TCP, which sends a heartbeat whenever idle.
+// It offers the compression of the codec first, and reads the stream to learn whether the peer accepts it.
+pub(crate) async fn send<S: AsyncRead + AsyncWrite + Unpin>(mut stream: FramedStream<S>, codec: EventCodec, mut rx: mpsc::Receiver<Arc<Event>>) {
+    if let Some(offer) = codec.offer() {
+        if let Err(e) = stream.send(offer.into()).await {
+            warn!(error = %e, "connection lost");
+            return;
+        }
+    }
+    let (mut last, mut open) = (Instant::now(), true);
+    loop {
+        select! {
+            _ = sleep_until(last + HEARTBEAT_INTERVAL) => {
+                if let Err(e) = stream.send(heartbeat_event().into()).await {
+                    warn!(error = %e, "connection lost");
+                    break;
+                }
+                last = Instant::now();
+            },
+            event = rx.recv() => {
+                let Some(event) = event else { break };
+                debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+                if let Err(e) = stream.send(event).await {
+                    warn!(error = %e, "send failed");
+                }
+                last = Instant::now();
+            },
+            msg = stream.next(), if open => {
+                match msg {
+                    Some(Ok(event)) => codec.accept(&event),
+                    Some(Err(e)) if is_too_large(&e) => {
+                        warn!(error = %e, "frame rejected");
+                        break;
+                    },
+                    Some(Err(e)) => warn!(error = %e, "invalid frame"),
+                    None => open = false,
+                }
+            },
+        }
+    }
+}
+
+// Stream handler of a duplex listener, which subscribes the peer once it advertises its interest.
+async fn process_duplex(stream: FramedStream<TcpStream>, peer: SocketAddr, tx: mpsc::Sender<Event>, duplex: Duplex, idle_timeout: Option<Duration>, token: CancellationToken) {
+    let (mut sink, mut stream) = stream.split();
+    let mut subscription: Option<(SubscriptionHandle, Inbox)> = None;
+    let mut last = Instant::now();
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            _ = expired(last, idle_timeout) => {
+                warn!("connection lost");
+                let _ = tx.send(connection_lost_event(&peer.to_string())).await;
+                break;
+            },
+            Some(event) = next_dispatch(&mut subscription) => {
+                debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+                if let Err(e) = sink.send(event).await {
+                    if is_too_large(&e) {
+                        warn!(error = %e, "event dropped");
+                        continue;
+                    }
+                    warn!(error = %e, "connection lost");
+                    break;
+                }
+            },
+            msg = stream.next() => {
+                last = Instant::now();
+                match msg {
+                    Some(Ok(event)) if is_heartbeat(&event) => {},
+                    Some(Ok(event)) if is_negotiation(&event) => {
+                        if let Some(reply) = accept_compression(&event) {
+                            if let Err(e) = sink.send(reply.into()).await {
+                                warn!(error = %e, "connection lost");
+                                break;
+                            }
+                        }
+                    },
+                    Some(Ok(event)) if event.topic == DUPLEX_TOPIC => {
+                        let interest = match advertised(&event, &duplex.allowed, peer) {
+                            Ok(interest) => interest,
+                            Err(e) => {
+                                warn!(error = %e, "invalid interest");
+                                continue;
+                            },
+                        };
+                        let result = match &subscription {
+                            Some((handle, _)) => handle.set_interest(interest).await,
+                            None => {
+                                let (sub, inbox) = Subscription::new(interest, duplex.buffer, Backpressure::DropNewest);
+                                subscription = Some((sub.handle(duplex.dispatcher.clone()), inbox));
+                                duplex.dispatcher.send(Command::Subscribe(sub.advertised(false))).await.map_err(Error::from)
+                            },
+                        };
+                        if let Err(e) = result {
+                            warn!(error = %e, "subscription failed");
+                            break;
+                        }
+                    },
+                    Some(Ok(mut event)) => {
+                        event.headers.insert(String::from(SOURCE_HEADER), HeaderValue::from(peer.to_string()));
+                        debug!(kind = "IN", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "received");
+                        let _ = tx.send(event).await;
+                    },
+                    Some(Err(e)) if is_too_large(&e) => {
+                        warn!(error = %e, "frame rejected");
+                        break;
+                    },
+                    Some(Err(e)) => warn!(error = %e, "invalid frame"),
+                    None => break,
+                }
+            },
+        }
+    }
+    if let Some((handle, _)) = subscription {
+        let _ = handle.cancel().await;
+    }
+}
+
+// Receives the next `Event` dispatched to the peer, waiting forever if it is not subscribed yet.
+async fn next_dispatch(subscription: &mut Option<(SubscriptionHandle, Inbox)>) -> Option<Arc<Event>> {
+    match subscription {
+        Some((_, inbox)) => inbox.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+// Builds the interest advertised by a peer, restricted to the allowed one and excluding the `Event`s received from the peer itself.
+fn advertised(event: &Event, allowed: &Interest, peer: SocketAddr) -> Result<Interest> {
+    let spec: InterestSpec = serde_json::from_slice(&event.data).map_err(|e| Error::Codec(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    Ok(Interest::All(vec![allowed.clone(), spec.build()?, remote::not_from(&peer.to_string())]))
+}
+
+/// Runs a new task acting as a TCP sender to a given socket, which advertises an interest with `DUPLEX_TOPIC`
+/// and receives back on the same connection the matching `Event`s, if the listener is a duplex one.
+/// 
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `interest` : the configuration of the `Interest` advertised to the listener.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward to the TCP stream.
+/// - `tx` : a transmitter to send back the `Event`s pushed by the listener.
+pub async fn new_duplex_sender<T: ToSocketAddrs>(addr: T, framing: FramingConfig, interest: &InterestSpec, rx: mpsc::Receiver<Arc<Event>>, tx: mpsc::Sender<Event>) -> Result<()> {
+    let data = serde_json::to_vec(interest).map_err(|e| Error::Codec(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    let stream = TcpStream::connect(addr).await.map_err(Error::Connect)?;
+    let peer = stream.peer_addr().map_err(Error::Connect)?;
+    let mut stream = frame_stream_with(stream, EventCodec::new(framing));
+    stream.send(Event::new(DUPLEX_TOPIC, Bytes::from(data)).into()).await.map_err(Error::Connect)?;
+    tokio::spawn(async move {
+        send_duplex(stream, peer, rx, tx).await;
+    }.instrument(info_span!("connection", protocol = "TCP", %peer)));
+    Ok(())
+}
+
+/// Runs a new task acting as a TCP sender to a given socket, which links with the listener to exchange the interests
+/// of their local `Subscription`s, as described in the `remote` module.
+/// 
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `allowed` : the `Interest` that the `Event`s sent to the listener must match, besides the advertised ones.
+/// - `tx` : a transmitter to send back the `Event`s received from the listener.
+/// - `dispatcher` : the `Dispatcher` whose `Subscription`s are advertised, and to which the listener is subscribed.
+/// - `buffer` : the number of `Event`s buffered for the listener.
+/// - `token` : cancellation token for handling termination.
+#[allow(clippy::too_many_arguments)]
+pub async fn new_link_sender<T: ToSocketAddrs>(addr: T, framing: FramingConfig, allowed: Interest, tx: mpsc::Sender<Event>, dispatcher: mpsc::Sender<Command>, buffer: usize, token: CancellationToken) -> Result<()> {
+    let stream = TcpStream::connect(addr).await.map_err(Error::Connect)?;
+    let peer = stream.peer_addr().map_err(Error::Connect)?;
+    let codec = EventCodec::new(framing);
+    let stream = frame_stream_with(stream, codec.clone());
+    tokio::spawn(async move {
+        remote::link(stream, codec, peer.to_string(), allowed, tx, dispatcher, buffer, None, token).await;
+    }.instrument(info_span!("connection", protocol = "TCP", %peer)));
     Ok(())
 }
 
-// Sender task
-async fn send(mut stream: FramedStream<TcpStream>, mut rx: mpsc::Receiver<Event>) {
-    while let Some(event) = rx.recv().await {
-        println!("\x1b[94mOUT\x1b[0m [{}] {} - \"{}\" = {} Bytes", Utc::now(), &event.timestamp, &event.topic, event.data.len());
-        let _ = stream.send(event).await;
+// Sender task of a duplex sender, which also receives the `Event`s pushed back by the listener.
+async fn send_duplex(stream: FramedStream<TcpStream>, peer: SocketAddr, mut rx: mpsc::Receiver<Arc<Event>>, tx: mpsc::Sender<Event>) {
+    let (mut sink, mut stream) = stream.split();
+    let mut sent = Instant::now();
+    loop {
+        select! {
+            _ = sleep_until(sent + HEARTBEAT_INTERVAL) => {
+                sent = Instant::now();
+                if let Err(e) = sink.send(heartbeat_event().into()).await {
+                    warn!(error = %e, "connection lost");
+                    break;
+                }
+            },
+            event = rx.recv() => {
+                let event = match event {
+                    Some(event) => event,
+                    None => break,
+                };
+                sent = Instant::now();
+                debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+                if let Err(e) = sink.send(event).await {
+                    warn!(error = %e, "send failed");
+                }
+            },
+            msg = stream.next() => {
+                match msg {
+                    Some(Ok(mut event)) => {
+                        event.headers.insert(String::from(SOURCE_HEADER), HeaderValue::from(peer.to_string()));
+                        debug!(kind = "IN", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "received");
+                        let _ = tx.send(event).await;
+                    },
+                    Some(Err(e)) if is_too_large(&e) => {
+                        warn!(error = %e, "frame rejected");
+                        break;
+                    },
+                    Some(Err(e)) => warn!(error = %e, "invalid frame"),
+                    None => {
+                        warn!("connection lost");
+                        break;
+                    },
+                }
+            },
+        }
+    }
+}
+
+/// `Transport` of the TCP protocol, also serving as an example for the implementation of custom ones.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TcpTransport;
+
+impl Transport for TcpTransport {
+    fn bind<'a>(&'a self, channel: &'a Channel) -> BoxFuture<'a, Result<Box<dyn Listener>>> {
+        Box::pin(async move {
+            let listener = TcpListener::bind(&channel.address).await.map_err(Error::Bind)?;
+            Ok(Box::new(FramedListener(listener, channel.framing())) as Box<dyn Listener>)
+        })
+    }
+
+    fn connect<'a>(&'a self, channel: &'a Channel) -> BoxFuture<'a, Result<Connection>> {
+        Box::pin(async move {
+            let stream = TcpStream::connect(&channel.address).await.map_err(Error::Connect)?;
+            let peer = stream.peer_addr().map_err(Error::Connect)?;
+            Ok(Connection::framed(peer.to_string(), stream, channel.framing()))
+        })
+    }
+}
+
+// Listener of the `TcpTransport`, framing the accepted connections as configured by its channel.
+struct FramedListener(TcpListener, FramingConfig);
+
+impl Listener for FramedListener {
+    fn accept(&mut self) -> BoxFuture<'_, Result<Connection>> {
+        Box::pin(async move {
+            let (stream, peer) = self.0.accept().await?;
+            Ok(Connection::framed(peer.to_string(), stream, self.1))
+        })
+    }
+}
+
+/// Parameters of the exponential backoff between the connection attempts of a reconnecting sender.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    /// Delay after the first failed attempt.
+    pub initial: Duration,
+    /// Upper bound of the delay.
+    pub max: Duration,
+    /// Factor applied to the delay after each failed attempt.
+    pub multiplier: f64,
+    /// Fraction of the delay randomly added or subtracted, to avoid synchronized reconnections of many senders.
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns the delay before the next attempt, after `attempt` consecutive failed ones.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let base = self.initial.as_secs_f64() * self.multiplier.powi(attempt.min(i32::MAX as u32) as i32);
+        let base = base.min(self.max.as_secs_f64());
+        let unit = random_u64() as f64 / u64::MAX as f64;
+        let jitter = base * self.jitter * (2.0 * unit - 1.0);
+        Duration::from_secs_f64((base + jitter).max(0.0))
+    }
+}
+
+/// States of the connection of a reconnecting sender.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// A connection attempt is in progress.
+    Connecting,
+    /// The stream is connected to the given peer.
+    Connected(SocketAddr),
+    /// The last connection attempt failed, or the stream was lost, and the sender is waiting to retry.
+    Disconnected,
+}
+
+/// Runs a new task acting as a TCP sender to a given socket, which reconnects with exponential backoff whenever
+/// the connection fails or is lost.
+/// 
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward to the TCP stream.
+/// - `backoff` : the parameters of the delay between the connection attempts.
+/// - `capacity` : the maximum number of `Event`s buffered while disconnected, after which the oldest ones are dropped.
+/// - `token` : cancellation token for handling termination.
+/// 
+/// # Returns
+/// - A receiver of the changes of the `ConnectionState`.
+pub fn new_reconnecting_sender<T>(addr: T, framing: FramingConfig, rx: mpsc::Receiver<Arc<Event>>, backoff: Backoff, capacity: usize, token: CancellationToken) -> watch::Receiver<ConnectionState>
+where
+    T: ToSocketAddrs + Clone + Send + Sync + 'static,
+{
+    let connect = move || {
+        let addr = addr.clone();
+        async move {
+            let stream = TcpStream::connect(addr).await?;
+            Ok((stream.peer_addr()?, stream))
+        }
+    };
+    spawn_reconnecting(connect, "TCP", framing, rx, backoff, capacity, token)
+}
+
+// Runs a reconnecting sender task over the streams returned by `connect`, shared by the protocols running on top of TCP.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn spawn_reconnecting<C, F, S>(connect: C, protocol: &'static str, framing: FramingConfig, rx: mpsc::Receiver<Arc<Event>>, backoff: Backoff, capacity: usize, token: CancellationToken) -> watch::Receiver<ConnectionState>
+where
+    C: Fn() -> F + Send + 'static,
+    F: Future<Output = io::Result<(SocketAddr, S)>> + Send + 'static,
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (state_tx, state_rx) = watch::channel(ConnectionState::Connecting);
+    let sender = Reconnecting {
+        rx,
+        pending: VecDeque::new(),
+        capacity,
+        closed: false,
+        framing,
+    };
+    tokio::spawn(sender.run(connect, protocol, backoff, state_tx, token));
+    state_rx
+}
+
+// Outcome of a connected session of a reconnecting sender.
+enum Session {
+    Lost,
+    Closed,
+}
+
+// State of a reconnecting sender task.
+struct Reconnecting {
+    rx: mpsc::Receiver<Arc<Event>>,
+    pending: VecDeque<Arc<Event>>,
+    capacity: usize,
+    closed: bool,
+    framing: FramingConfig,
+}
+
+impl Reconnecting {
+    // Reconnecting sender task
+    async fn run<C, F, S>(mut self, connect: C, protocol: &'static str, backoff: Backoff, state: watch::Sender<ConnectionState>, token: CancellationToken)
+    where
+        C: Fn() -> F,
+        F: Future<Output = io::Result<(SocketAddr, S)>>,
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut attempt = 0;
+        loop {
+            let _ = state.send(ConnectionState::Connecting);
+            let result = match self.buffer_until(connect(), &token).await {
+                Some(result) => result,
+                None => break,
+            };
+            match result {
+                Ok((peer, stream)) => {
+                    attempt = 0;
+                    let _ = state.send(ConnectionState::Connected(peer));
+                    let codec = EventCodec::new(self.framing);
+                    let session = self.forward(frame_stream_with(stream, codec.clone()), codec, &token).instrument(info_span!("connection", protocol, %peer)).await;
+                    if let Session::Closed = session {
+                        break;
+                    }
+                    warn!(%peer, "connection lost");
+                },
+                Err(e) => warn!(error = %e, attempt, "connection failed"),
+            };
+            let _ = state.send(ConnectionState::Disconnected);
+            let delay = backoff.delay(attempt);
+            attempt = attempt.saturating_add(1);
+            if self.buffer_until(sleep(delay), &token).await.is_none() {
+                break;
+            }
+        }
+    }
+
+    // Buffers the incoming `Event`s until the future completes, returning `None` if the task must terminate.
+    async fn buffer_until<F: Future>(&mut self, future: F, token: &CancellationToken) -> Option<F::Output> {
+        tokio::pin!(future);
+        loop {
+            select! {
+                _ = token.cancelled() => return None,
+                output = &mut future => return Some(output),
+                event = self.rx.recv(), if !self.closed => {
+                    match event {
+                        Some(event) => self.push(event),
+                        None => {
+                            self.closed = true;
+                            if self.pending.is_empty() {
+                                return None;
+                            }
+                        },
+                    }
+                },
+            }
+        }
+    }
+
+    // Sends the buffered and then the incoming `Event`s, until the stream is lost or the task must terminate.
+    async fn forward<S: AsyncRead + AsyncWrite + Unpin>(&mut self, mut stream: FramedStream<S>, codec: EventCodec, token: &CancellationToken) -> Session {
+        if let Some(offer) = codec.offer() {
+            if let Err(e) = stream.send(offer.into()).await {
+                warn!(error = %e, "send failed");
+                return Session::Lost;
+            }
+        }
+        while let Some(event) = self.pending.pop_front() {
+            if let Err(e) = self.send(&mut stream, event).await {
+                warn!(error = %e, "send failed");
+                return Session::Lost;
+            }
+        }
+        if self.closed {
+            return Session::Closed;
+        }
+        loop {
+            select! {
+                _ = token.cancelled() => return Session::Closed,
+                _ = sleep(HEARTBEAT_INTERVAL) => {
+                    if let Err(e) = stream.send(heartbeat_event().into()).await {
+                        warn!(error = %e, "send failed");
+                        return Session::Lost;
+                    }
+                },
+                event = self.rx.recv() => {
+                    match event {
+                        Some(event) => {
+                            if let Err(e) = self.send(&mut stream, event).await {
+                                warn!(error = %e, "send failed");
+                                return Session::Lost;
+                            }
+                        },
+                        None => return Session::Closed,
+                    }
+                },
+                msg = stream.next() => {
+                    match msg {
+                        Some(Ok(event)) => codec.accept(&event),
+                        Some(Err(e)) if is_too_large(&e) => {
+                            warn!(error = %e, "frame rejected");
+                            return Session::Lost;
+                        },
+                        Some(Err(e)) => warn!(error = %e, "invalid frame"),
+                        None => return Session::Lost,
+                    }
+                },
+            }
+        }
+    }
+
+    // Sends an `Event`, keeping it buffered if the stream fails, or dropping it if its frame is too large.
+    async fn send<S: AsyncRead + AsyncWrite + Unpin>(&mut self, stream: &mut FramedStream<S>, event: Arc<Event>) -> io::Result<()> {
+        debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+        if let Err(e) = stream.send(event.clone()).await {
+            if is_too_large(&e) {
+                warn!(error = %e, topic = %event.topic, "event dropped");
+                return Ok(());
+            }
+            self.pending.push_front(event);
+            return Err(e);
+        }
+        Ok(())
     }
-}
\ No newline at end of file
+
+    // Buffers an `Event` while disconnected, dropping the oldest one if the buffer is full.
+    fn push(&mut self, event: Arc<Event>) {
+        if self.pending.len() >= self.capacity {
+            if let Some(dropped) = self.pending.pop_front() {
+                warn!(topic = %dropped.topic, "buffer full, event dropped");
+            }
+        }
+        self.pending.push_back(event);
+    }
+}
@@ -0,0 +1,198 @@
+//! This module offers functions to use TLS over TCP, through `rustls`, for sending and receiving `Event`s.
+//!
+//! Once the handshake is completed, the streams are handled as the TCP ones.
+
+use std::{fmt::Display, io, net::SocketAddr, sync::Arc, time::Duration};
+
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use rustls::crypto::{CryptoProvider, ring};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject};
+use rustls::server::WebPkiClientVerifier;
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::select;
+use tokio::sync::{mpsc, watch};
+use tokio_rustls::{TlsAcceptor, TlsConnector, client::TlsStream};
+use tokio_util::sync::CancellationToken;
+
+use tracing::{Instrument, info_span, warn};
+
+use super::tcp::{self, Backoff, ConnectionState};
+use crate::framing::{EventCodec, FramingConfig, frame_stream_with};
+use crate::{Error, Event, error::Result};
+
+/// TLS settings of a channel, referring to PEM files.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// Path of the certificate chain of this node, required by receivers, and used by senders to authenticate themselves.
+    pub cert: Option<String>,
+    /// Path of the private key of the certificate.
+    pub key: Option<String>,
+    /// Path of the certificates of the trusted authorities, required by senders to verify the receiver.
+    /// If set for a receiver, the senders must present a certificate signed by one of them.
+    pub ca: Option<String>,
+    /// Name verified against the certificate of the receiver, by default the host of the address.
+    pub server_name: Option<String>,
+}
+
+impl TlsConfig {
+    /// Builds the configuration of a receiver, failing if the certificate or the key are missing or invalid.
+    pub fn server_config(&self) -> Result<Arc<ServerConfig>> {
+        let builder = ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+        let builder = match &self.ca {
+            Some(ca) => {
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots(ca)?), provider()).build().map_err(invalid)?;
+                builder.with_client_cert_verifier(verifier)
+            },
+            None => builder.with_no_client_auth(),
+        };
+        let (certs, key) = self.identity()?.ok_or_else(|| invalid("missing certificate"))?;
+        Ok(Arc::new(builder.with_single_cert(certs, key)?))
+    }
+
+    /// Builds the configuration of a sender, failing if the trusted authorities are missing or invalid.
+    pub fn client_config(&self) -> Result<Arc<ClientConfig>> {
+        let ca = self.ca.as_ref().ok_or_else(|| invalid("missing trusted authorities"))?;
+        let builder = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots(ca)?);
+        let config = match self.identity()? {
+            Some((certs, key)) => builder.with_client_auth_cert(certs, key)?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(Arc::new(config))
+    }
+
+    /// Returns the name verified against the certificate of the receiver with the given address.
+    pub fn server_name(&self, address: &str) -> Result<ServerName<'static>> {
+        let name = match &self.server_name {
+            Some(name) => name.as_str(),
+            None => address.rsplit_once(':').map_or(address, |(host, _)| host).trim_start_matches('[').trim_end_matches(']'),
+        };
+        ServerName::try_from(name.to_string()).map_err(|_| Error::Address)
+    }
+
+    // Loads the certificate chain and its private key, if configured.
+    fn identity(&self) -> Result<Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>> {
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => {
+                let certs = CertificateDer::pem_file_iter(cert).map_err(invalid)?.collect::<std::result::Result<_, _>>().map_err(invalid)?;
+                Ok(Some((certs, PrivateKeyDer::from_pem_file(key).map_err(invalid)?)))
+            },
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(invalid("missing private key")),
+            (None, Some(_)) => Err(invalid("missing certificate")),
+        }
+    }
+}
+
+// Returns the cryptographic provider, explicitly chosen to not depend on the process-wide default.
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+// Loads the certificates of the trusted authorities.
+fn roots(path: &str) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(path).map_err(invalid)? {
+        roots.add(cert.map_err(invalid)?)?;
+    }
+    Ok(roots)
+}
+
+// Returns the error of an invalid TLS setting.
+fn invalid(e: impl Display) -> Error {
+    Error::Tls(rustls::Error::General(e.to_string()))
+}
+
+/// Runs a new task acting as a listener on a given socket, accepting only TLS streams.
+///
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `config` : the TLS configuration of the listener, e.g. built by `TlsConfig::server_config()`.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `tx` : a transmitter to send back the `Event`s received from the TLS streams.
+/// - `idle_timeout` : the time without receiving anything from a peer after which it is considered lost, if any.
+///
+/// # Returns
+/// - cancellation token for handling termination.
+pub async fn new_receiver<T: ToSocketAddrs>(addr: T, config: Arc<ServerConfig>, framing: FramingConfig, tx: mpsc::Sender<Event>, idle_timeout: Option<Duration>, token: CancellationToken) -> Result<()> {
+    let listener = TcpListener::bind(addr).await.map_err(Error::Bind)?;
+    tokio::spawn(async move {
+        listen(listener, TlsAcceptor::from(config), framing, tx, idle_timeout, token).await;
+    });
+    Ok(())
+}
+
+// Listener task
+async fn listen(listener: TcpListener, acceptor: TlsAcceptor, framing: FramingConfig, tx: mpsc::Sender<Event>, idle_timeout: Option<Duration>, token: CancellationToken) {
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            Ok((stream, peer)) = listener.accept() => {
+                let acceptor = acceptor.clone();
+                let clone = tx.clone();
+                let child = token.child_token();
+                tokio::spawn(async move {
+                    let stream = select! {
+                        _ = child.cancelled() => return,
+                        result = acceptor.accept(stream) => match result {
+                            Ok(stream) => stream,
+                            Err(e) => return warn!(error = %e, "handshake failed"),
+                        },
+                    };
+                    tcp::process(frame_stream_with(stream, EventCodec::new(framing)), peer, clone, idle_timeout, child).await;
+                }.instrument(info_span!("connection", protocol = "TLS", %peer)));
+            },
+        }
+    }
+}
+
+/// Runs a new task acting as a TLS sender to a given socket.
+///
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `server_name` : the name verified against the certificate of the listener.
+/// - `config` : the TLS configuration of the sender, e.g. built by `TlsConfig::client_config()`.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward to the TLS stream.
+pub async fn new_sender<T: ToSocketAddrs>(addr: T, server_name: ServerName<'static>, config: Arc<ClientConfig>, framing: FramingConfig, rx: mpsc::Receiver<Arc<Event>>) -> Result<()> {
+    let (peer, stream) = connect(addr, server_name, TlsConnector::from(config)).await.map_err(Error::Connect)?;
+    let codec = EventCodec::new(framing);
+    tokio::spawn(async move {
+        tcp::send(frame_stream_with(stream, codec.clone()), codec, rx).await;
+    }.instrument(info_span!("connection", protocol = "TLS", %peer)));
+    Ok(())
+}
+
+/// Runs a new task acting as a TLS sender to a given socket, which reconnects with exponential backoff whenever
+/// the connection or the handshake fails, or the connection is lost.
+///
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `server_name` : the name verified against the certificate of the listener.
+/// - `config` : the TLS configuration of the sender, e.g. built by `TlsConfig::client_config()`.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward to the TLS stream.
+/// - `backoff` : the parameters of the delay between the connection attempts.
+/// - `capacity` : the maximum number of `Event`s buffered while disconnected, after which the oldest ones are dropped.
+/// - `token` : cancellation token for handling termination.
+///
+/// # Returns
+/// - A receiver of the changes of the `ConnectionState`.
+#[allow(clippy::too_many_arguments)]
+pub fn new_reconnecting_sender<T>(addr: T, server_name: ServerName<'static>, config: Arc<ClientConfig>, framing: FramingConfig, rx: mpsc::Receiver<Arc<Event>>, backoff: Backoff, capacity: usize, token: CancellationToken) -> watch::Receiver<ConnectionState>
+where
+    T: ToSocketAddrs + Clone + Send + Sync + 'static,
+{
+    let connector = TlsConnector::from(config);
+    let connect = move || connect(addr.clone(), server_name.clone(), connector.clone());
+    tcp::spawn_reconnecting(connect, "TLS", framing, rx, backoff, capacity, token)
+}
+
+// Connects to the listener and completes the handshake.
+async fn connect<T: ToSocketAddrs>(addr: T, server_name: ServerName<'static>, connector: TlsConnector) -> io::Result<(SocketAddr, TlsStream<TcpStream>)> {
+    let stream = TcpStream::connect(addr).await?;
+    let peer = stream.peer_addr()?;
+    Ok((peer, connector.connect(server_name, stream).await?))
+}
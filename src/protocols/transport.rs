@@ -0,0 +1,197 @@
+//! This module offers the `Transport` trait, allowing other crates to add their own protocols, and the `Registry`
+//! through which the configured channels with a `Protocol::Custom` refer to them by name.
+
+use std::{collections::HashMap, fmt::Debug, sync::Arc, time::Duration};
+
+use futures::{Sink, SinkExt, Stream, StreamExt, future::BoxFuture};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+use tracing::{Instrument, debug, info_span, warn};
+
+use super::heartbeat::is_heartbeat;
+use crate::config::Channel;
+use crate::framing::{EventCodec, FramingConfig, accept_compression, frame_stream_with, is_negotiation};
+use crate::{Error, Event, HeaderValue, SOURCE_HEADER, error::Result};
+
+// Time waited after a failed accept before the next one, so that a lasting failure, e.g. too many open files, does not spin.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Sink of the `Event`s sent to a peer.
+pub type EventSink = std::pin::Pin<Box<dyn Sink<Arc<Event>, Error = Error> + Send>>;
+/// Stream of the `Event`s received from a peer.
+pub type EventStream = std::pin::Pin<Box<dyn Stream<Item = Result<Event>> + Send>>;
+
+/// Connection to a peer, through which `Event`s are sent and received.
+pub struct Connection {
+    /// Identifier of the peer, stamped on the received `Event`s as `SOURCE_HEADER`.
+    pub peer: String,
+    pub sink: EventSink,
+    pub stream: EventStream,
+}
+
+impl Connection {
+    /// Creates a new `Connection` over a byte stream, framed as the ones of the TCP protocol with the given framing,
+    /// e.g. the one of the channel, offering its compression to the peer along with the first `Event` sent.
+    pub fn framed<S: AsyncRead + AsyncWrite + Send + 'static>(peer: String, stream: S, framing: FramingConfig) -> Self {
+        let codec = EventCodec::new(framing);
+        let mut offer = codec.offer().map(Arc::new);
+        let (sink, stream) = frame_stream_with(stream, codec.clone()).split();
+        let sink = sink.with_flat_map(move |event| futures::stream::iter(offer.take().into_iter().chain(Some(event)).map(Ok)));
+        let stream = stream.inspect(move |event| {
+            if let Ok(event) = event {
+                codec.accept(event);
+            }
+        });
+        Self {
+            peer,
+            sink: Box::pin(sink.sink_map_err(Error::Io)),
+            stream: Box::pin(stream.map(|event| event.map_err(Error::Codec))),
+        }
+    }
+}
+
+/// Listener accepting the `Connection`s of the peers.
+pub trait Listener: Send {
+    /// Waits for the next `Connection`.
+    fn accept(&mut self) -> BoxFuture<'_, Result<Connection>>;
+}
+
+/// Protocol able to listen for and to establish `Connection`s, as configured by a `Channel`.
+pub trait Transport: Debug + Send + Sync {
+    /// Binds a `Listener` to the address of the channel.
+    fn bind<'a>(&'a self, channel: &'a Channel) -> BoxFuture<'a, Result<Box<dyn Listener>>>;
+
+    /// Establishes a `Connection` with the address of the channel.
+    fn connect<'a>(&'a self, channel: &'a Channel) -> BoxFuture<'a, Result<Connection>>;
+}
+
+/// Collection of the `Transport`s referred by name by the channels with a `Protocol::Custom`.
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    transports: HashMap<String, Arc<dyn Transport>>,
+}
+
+impl Registry {
+    /// Creates a new empty `Registry` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a `Transport` with the given name, replacing the previous one with the same name, if any.
+    ///
+    /// The names of the built-in `Protocol`s are parsed as such, so they cannot refer to registered `Transport`s.
+    pub fn register(&mut self, name: &str, transport: impl Transport + 'static) -> &mut Self {
+        self.transports.insert(name.to_string(), Arc::new(transport));
+        self
+    }
+
+    /// Returns the `Transport` with the given name, failing if not registered.
+    pub fn get(&self, name: &str) -> Result<Arc<dyn Transport>> {
+        self.transports.get(name).cloned().ok_or_else(|| Error::UnknownProtocol(name.to_string()))
+    }
+}
+
+/// Runs a new task acting as a listener of the given `Transport`, on the address of the channel.
+///
+/// # Parameters
+/// - `transport` : the protocol of the listener.
+/// - `channel` : the configuration of the listener.
+/// - `tx` : a transmitter to send back the `Event`s received from the `Connection`s.
+///
+/// # Returns
+/// - cancellation token for handling termination.
+pub async fn new_receiver(transport: Arc<dyn Transport>, channel: &Channel, tx: mpsc::Sender<Event>, token: CancellationToken) -> Result<()> {
+    let listener = transport.bind(channel).await?;
+    let protocol = channel.protocol.to_string();
+    tokio::spawn(async move {
+        listen(listener, protocol, tx, token).await;
+    });
+    Ok(())
+}
+
+// Listener task
+async fn listen(mut listener: Box<dyn Listener>, protocol: String, tx: mpsc::Sender<Event>, token: CancellationToken) {
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            connection = listener.accept() => {
+                match connection {
+                    Ok(connection) => {
+                        let span = info_span!("connection", %protocol, peer = %connection.peer);
+                        tokio::spawn(process(connection, tx.clone(), token.child_token()).instrument(span));
+                    },
+                    Err(e) => {
+                        warn!(%protocol, error = %e, "accept failed");
+                        select! {
+                            _ = token.cancelled() => break,
+                            _ = sleep(ACCEPT_BACKOFF) => {},
+                        }
+                    },
+                }
+            },
+        }
+    }
+}
+
+// Connection handler
+async fn process(mut connection: Connection, tx: mpsc::Sender<Event>, token: CancellationToken) {
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            message = connection.stream.next() => {
+                match message {
+                    Some(Ok(event)) if is_heartbeat(&event) => {},
+                    Some(Ok(event)) if is_negotiation(&event) => {
+                        if let Some(reply) = accept_compression(&event) {
+                            if let Err(e) = connection.sink.send(reply.into()).await {
+                                warn!(error = %e, "connection lost");
+                                break;
+                            }
+                        }
+                    },
+                    Some(Ok(mut event)) => {
+                        event.headers.insert(String::from(SOURCE_HEADER), HeaderValue::from(connection.peer.clone()));
+                        debug!(kind = "IN", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "received");
+                        let _ = tx.send(event).await;
+                    },
+                    Some(Err(e)) => warn!(error = %e, "invalid frame"),
+                    None => break,
+                }
+            },
+        }
+    }
+}
+
+/// Runs a new task acting as a sender of the given `Transport`, to the address of the channel.
+///
+/// # Parameters
+/// - `transport` : the protocol of the sender.
+/// - `channel` : the configuration of the sender.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward to the `Connection`.
+pub async fn new_sender(transport: Arc<dyn Transport>, channel: &Channel, rx: mpsc::Receiver<Arc<Event>>) -> Result<()> {
+    let connection = transport.connect(channel).await?;
+    let span = info_span!("connection", protocol = %channel.protocol, peer = %connection.peer);
+    tokio::spawn(send(connection, rx).instrument(span));
+    Ok(())
+}
+
+// Sender task, which also reads the `Connection`, so that the framed ones learn whether the peer accepts their compression.
+async fn send(mut connection: Connection, mut rx: mpsc::Receiver<Arc<Event>>) {
+    let mut open = true;
+    loop {
+        select! {
+            event = rx.recv() => {
+                let Some(event) = event else { break };
+                debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+                if let Err(e) = connection.sink.send(event).await {
+                    warn!(error = %e, "send failed");
+                }
+            },
+            message = connection.stream.next(), if open => open = message.is_some(),
+        }
+    }
+}
@@ -1,87 +1,371 @@
 //! This module offers functions to use the UDP communication protocol for sending and receiving `Event`s.
+//!
+//! Each `Event` frame is split into `Packet::Fragment`s small enough to fit a datagram, each one acknowledged by the receiver
+//! and retransmitted by the sender until acknowledged. The frames of a sender are numbered within a random session,
+//! so that the receiver delivers them in order and discards the duplicates.
+//!
+//! The datagram mode, offered by `new_datagram_receiver()` and `new_datagram_sender()`, instead sends each `Event` frame
+//! as a single self-contained datagram, without acknowledgements: lost `Event`s are not retransmitted, and the ones
+//! too large for a datagram are rejected.
 
-use std::io::{Error, ErrorKind};
+use std::{collections::{BTreeMap, HashMap}, io, net::SocketAddr, sync::Arc, time::Duration};
 
-use tokio::net::{ToSocketAddrs, lookup_host};
+use bytes::{Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use tokio::net::{ToSocketAddrs, UdpSocket, lookup_host};
 use tokio::select;
 use tokio::sync::mpsc;
+use tokio::time::{Instant, sleep_until, timeout};
 use tokio_util::sync::CancellationToken;
 
-use udp_stream::{UdpListener, UdpStream};
+use tracing::{Instrument, debug, info_span, warn};
 
-use futures::{StreamExt, SinkExt};
+use super::heartbeat::{HEARTBEAT_INTERVAL, connection_lost_event, expired, heartbeat_event, is_heartbeat};
+use crate::framing::{DEFAULT_MAX_FRAME_LENGTH, decode, encode_shared};
+use crate::{Error, Event, HeaderValue, SOURCE_HEADER, error::Result, random_u64};
 
-use chrono::Utc;
+/// Maximum size, in bytes, of the payload of a fragment, chosen to fit the datagram in the usual MTU.
+pub const MAX_PAYLOAD: usize = 1200;
+/// Maximum number of fragments of a frame accepted by a receiver, i.e. of a frame up to `DEFAULT_MAX_FRAME_LENGTH`.
+pub const MAX_FRAGMENTS: u32 = DEFAULT_MAX_FRAME_LENGTH.div_ceil(MAX_PAYLOAD) as u32;
+/// Maximum number of unacknowledged fragments sent by a sender.
+pub const WINDOW: usize = 64;
+/// Time after which an unacknowledged fragment is retransmitted.
+pub const RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(200);
+/// Number of retransmissions of a fragment after which its frame is discarded.
+pub const MAX_RETRIES: u32 = 10;
+/// Time without receiving anything from a sender after which a receiver without idle timeout forgets its session.
+pub const SESSION_IDLE: Duration = Duration::from_secs(60);
 
-use crate::framing::{FramedStream, frame_stream};
-use crate::Event;
+/// Maximum size, in bytes, of the frame of an `Event` sent in datagram mode, i.e. the maximum payload of a UDP datagram.
+pub const MAX_DATAGRAM: usize = 65_507;
+
+// Size of the buffer for incoming datagrams, large enough for any UDP datagram.
+const DATAGRAM_BUFFER: usize = 65_536;
+
+// Datagram of the UDP transport.
+#[derive(Debug, Deserialize, Serialize)]
+enum Packet {
+    // Fragment `index` out of `count` of the `seq`-th frame of the session of a sender.
+    Fragment { session: u64, seq: u64, index: u32, count: u32, payload: Bytes },
+    // Acknowledgement of a received fragment.
+    Ack { session: u64, seq: u64, index: u32 },
+}
+
+impl Packet {
+    fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        bincode::serialize(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        bincode::deserialize(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
 
 /// Runs a new task acting as a listener on a given socket.
-/// 
+///
 /// # Parameters
 /// - `addr` : the socket address of the listener.
 /// - `tx` : a transmitter to send back the `Event`s received from the UDP communicaitons.
-/// 
+/// - `idle_timeout` : the time without receiving anything from a peer after which it is considered lost, if any.
+///
 /// # Returns
 /// - cancellation token for handling termination.
-pub async fn new_receiver<T: ToSocketAddrs>(addr: T, tx: mpsc::Sender<Event>, token: CancellationToken) -> Result<(), Box<dyn std::error::Error>> {
-    let listener = UdpListener::bind(lookup_host(addr).await?.next().ok_or(Error::new(ErrorKind::InvalidData, "address not found"))?).await?;
+pub async fn new_receiver<T: ToSocketAddrs>(addr: T, tx: mpsc::Sender<Event>, idle_timeout: Option<Duration>, token: CancellationToken) -> Result<()> {
+    let socket = UdpSocket::bind(resolve(addr).await?).await.map_err(Error::Bind)?;
     tokio::spawn(async move {
-        listen(listener, tx, token).await;
+        listen(socket, tx, idle_timeout, token).await;
     });
     Ok(())
 }
 
-// Listener task
-async fn listen(listener: UdpListener, tx: mpsc::Sender<Event>, token: CancellationToken) {
+// Resolves the first socket address
+pub(crate) async fn resolve<T: ToSocketAddrs>(addr: T) -> Result<SocketAddr> {
+    lookup_host(addr).await.map_err(|_| Error::Address)?.next().ok_or(Error::Address)
+}
+
+// Listener task, which forgets the peers silent for the idle timeout, or else `SESSION_IDLE`, notifying them as lost
+// only in the former case.
+async fn listen(socket: UdpSocket, tx: mpsc::Sender<Event>, idle_timeout: Option<Duration>, token: CancellationToken) {
+    let mut peers: HashMap<SocketAddr, Reassembly> = HashMap::new();
+    let mut buf = vec![0; DATAGRAM_BUFFER];
+    let forget = idle_timeout.unwrap_or(SESSION_IDLE);
     loop {
+        let oldest = peers.values().map(|reassembly| reassembly.last).min();
         select! {
             _ = token.cancelled() => break,
-            Ok((stream, _)) = listener.accept() => {
-                let stream = frame_stream(stream);
-                let clone = tx.clone();
-                let child = token.child_token();
-                tokio::spawn(async move {
-                    process(stream, clone, child).await;
-                });
+            _ = expired(oldest.unwrap_or_else(Instant::now), Some(forget).filter(|_| oldest.is_some())) => {
+                let now = Instant::now();
+                let lost: Vec<SocketAddr> = peers.iter()
+                    .filter(|(_, reassembly)| reassembly.last + forget <= now)
+                    .map(|(peer, _)| *peer)
+                    .collect();
+                for peer in lost {
+                    peers.remove(&peer);
+                    if idle_timeout.is_some() {
+                        warn!(protocol = "UDP", %peer, "connection lost");
+                        let _ = tx.send(connection_lost_event(&peer.to_string())).await;
+                    }
+                }
+            },
+            Ok((len, peer)) = socket.recv_from(&mut buf) => {
+                let (session, seq, index, count, payload) = match Packet::from_bytes(&buf[..len]) {
+                    Ok(Packet::Fragment { session, seq, index, count, payload }) => (session, seq, index, count, payload),
+                    Ok(Packet::Ack { .. }) => continue,
+                    Err(e) => {
+                        warn!(%peer, error = %e, "invalid datagram");
+                        continue;
+                    },
+                };
+                if let Ok(ack) = (Packet::Ack { session, seq, index }).to_bytes() {
+                    let _ = socket.send_to(&ack, peer).await;
+                }
+                let reassembly = peers.entry(peer).or_insert_with(|| Reassembly::new(session));
+                if reassembly.session != session {
+                    *reassembly = Reassembly::new(session);
+                }
+                reassembly.last = Instant::now();
+                if let Some(frame) = reassembly.push(seq, index, count, payload) {
+                    process(frame, peer, &tx).instrument(info_span!("connection", protocol = "UDP", %peer)).await;
+                }
             },
         }
     }
 }
 
-// Stream handler
-async fn process(mut stream: FramedStream<UdpStream>, tx: mpsc::Sender<Event>, token: CancellationToken) {
-    loop {
-        select! {
-            _ = token.cancelled() => break,
-        Some(msg) = stream.next() => {
-            if let Ok(event) = msg {
-                println!("\x1b[96mIN\x1b[0m [{}] {} - \"{}\" = {} Bytes", Utc::now(), &event.timestamp, &event.topic, event.data.len());
-                let _ = tx.send(event).await;
-            }
+// Frame handler
+async fn process(frame: Bytes, peer: SocketAddr, tx: &mpsc::Sender<Event>) {
+    match decode(&frame) {
+        Ok(event) if is_heartbeat(&event) => {},
+        Ok(mut event) => {
+            event.headers.insert(String::from(SOURCE_HEADER), HeaderValue::from(peer.to_string()));
+            debug!(kind = "IN", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "received");
+            let _ = tx.send(event).await;
         },
+        Err(e) => warn!(error = %e, "invalid frame"),
+    }
+}
+
+// Reassembly state of the frames of the session of a sender.
+struct Reassembly {
+    session: u64,
+    // Sequence number of the next frame to deliver, the ones before it are duplicates.
+    next: u64,
+    partial: Option<Partial>,
+    // Instant of the last fragment received from the sender.
+    last: Instant,
+}
+
+// Fragments received so far of a frame, stored as they come rather than allocated upfront for all of them.
+struct Partial {
+    seq: u64,
+    count: u32,
+    fragments: BTreeMap<u32, Bytes>,
+}
+
+impl Reassembly {
+    fn new(session: u64) -> Self {
+        Self {
+            session,
+            next: 0,
+            partial: None,
+            last: Instant::now(),
+        }
+    }
+
+    // Stores a fragment, returning the reassembled frame once all of its fragments are received.
+    //
+    // A sender transmits one frame at a time, so a fragment of a later frame means the previous one has been abandoned.
+    fn push(&mut self, seq: u64, index: u32, count: u32, payload: Bytes) -> Option<Bytes> {
+        if seq < self.next || index >= count || count > MAX_FRAGMENTS || payload.len() > MAX_PAYLOAD {
+            return None;
+        }
+        let partial = match &mut self.partial {
+            Some(partial) if partial.seq == seq => partial,
+            Some(partial) if partial.seq > seq => return None,
+            partial => partial.insert(Partial {
+                seq,
+                count,
+                fragments: BTreeMap::new(),
+            }),
+        };
+        if count != partial.count {
+            return None;
+        }
+        partial.fragments.entry(index).or_insert(payload);
+        if partial.fragments.len() != partial.count as usize {
+            return None;
+        }
+        let mut frame = BytesMut::with_capacity(partial.fragments.values().map(Bytes::len).sum());
+        for fragment in partial.fragments.values() {
+            frame.extend_from_slice(fragment);
         }
+        self.next = seq + 1;
+        self.partial = None;
+        Some(frame.freeze())
     }
 }
 
 /// Runs a new task acting as a UDP sender to a given socket.
-/// 
+///
 /// # Parameters
 /// - `addr` : the socket address of the listener.
 /// - `rx` : a receiver to use as the source of the `Event`s to forward to the UDP channel.
-pub async fn new_sender<T: ToSocketAddrs>(addr: T, rx: mpsc::Receiver<Event>) -> Result<(), Box<dyn std::error::Error>> {
-    let stream = UdpStream::connect(lookup_host(addr).await?.next().ok_or(Error::new(ErrorKind::InvalidData, "address not found"))?).await?;
-    let stream = frame_stream(stream);
+pub async fn new_sender<T: ToSocketAddrs>(addr: T, rx: mpsc::Receiver<Arc<Event>>) -> Result<()> {
+    let (socket, peer) = connect(addr).await?;
     tokio::spawn(async move {
-        send(stream, rx).await;
+        send(socket, rx).await;
+    }.instrument(info_span!("connection", protocol = "UDP", %peer)));
+    Ok(())
+}
+
+// Binds a socket to an ephemeral port and connects it to the first resolved address.
+async fn connect<T: ToSocketAddrs>(addr: T) -> Result<(UdpSocket, SocketAddr)> {
+    let peer = resolve(addr).await?;
+    let local: SocketAddr = if peer.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+    let socket = UdpSocket::bind(local).await.map_err(Error::Connect)?;
+    socket.connect(peer).await.map_err(Error::Connect)?;
+    Ok((socket, peer))
+}
+
+//Sender task, which sends a heartbeat whenever idle.
+async fn send(socket: UdpSocket, mut rx: mpsc::Receiver<Arc<Event>>) {
+    let session = random_u64();
+    let mut seq = 0;
+    loop {
+        let event = match timeout(HEARTBEAT_INTERVAL, rx.recv()).await {
+            Ok(Some(event)) => event,
+            Ok(None) => break,
+            Err(_) => heartbeat_event().into(),
+        };
+        debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+        let result = match encode_shared(&event) {
+            Ok(frame) => transmit(&socket, session, seq, frame).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!(error = %e, "send failed");
+        }
+        seq += 1;
+    }
+}
+
+// Sends the fragments of a frame, keeping up to `WINDOW` of them in flight, until all of them are acknowledged.
+async fn transmit(socket: &UdpSocket, session: u64, seq: u64, frame: Bytes) -> io::Result<()> {
+    let count = frame.len().div_ceil(MAX_PAYLOAD);
+    if count > MAX_FRAGMENTS as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("frame of {} bytes too large", frame.len())));
+    }
+    let fragment = |index: usize| Packet::Fragment {
+        session,
+        seq,
+        index: index as u32,
+        count: count as u32,
+        payload: frame.slice(index * MAX_PAYLOAD..frame.len().min((index + 1) * MAX_PAYLOAD)),
+    }.to_bytes();
+    // Deadline and retransmissions of every unacknowledged fragment.
+    let mut in_flight: BTreeMap<usize, (Instant, u32)> = BTreeMap::new();
+    let mut next = 0;
+    let mut buf = vec![0; DATAGRAM_BUFFER];
+    loop {
+        // Send errors are handled as lost datagrams, as the ones reported when the peer is unreachable.
+        while next < count && in_flight.len() < WINDOW {
+            let _ = socket.send(&fragment(next)?).await;
+            in_flight.insert(next, (Instant::now() + RETRANSMIT_TIMEOUT, 0));
+            next += 1;
+        }
+        let deadline = match in_flight.values().map(|(deadline, _)| *deadline).min() {
+            Some(deadline) => deadline,
+            None => return Ok(()),
+        };
+        select! {
+            result = socket.recv(&mut buf) => {
+                if let Ok(len) = result {
+                    if let Ok(Packet::Ack { session: s, seq: q, index }) = Packet::from_bytes(&buf[..len]) {
+                        if s == session && q == seq {
+                            in_flight.remove(&(index as usize));
+                        }
+                    }
+                }
+            },
+            _ = sleep_until(deadline) => {
+                let now = Instant::now();
+                for (index, (deadline, retries)) in in_flight.iter_mut() {
+                    if *deadline > now {
+                        continue;
+                    }
+                    if *retries == MAX_RETRIES {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, format!("fragment {} not acknowledged", index)));
+                    }
+                    let _ = socket.send(&fragment(*index)?).await;
+                    *deadline = now + RETRANSMIT_TIMEOUT;
+                    *retries += 1;
+                }
+            },
+        }
+    }
+}
+
+/// Runs a new task acting as a listener on a given socket, receiving one `Event` per datagram.
+///
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `tx` : a transmitter to send back the `Event`s received from the UDP datagrams.
+pub async fn new_datagram_receiver<T: ToSocketAddrs>(addr: T, tx: mpsc::Sender<Event>, token: CancellationToken) -> Result<()> {
+    let socket = UdpSocket::bind(resolve(addr).await?).await.map_err(Error::Bind)?;
+    tokio::spawn(async move {
+        listen_datagrams(socket, tx, token).await;
     });
     Ok(())
 }
 
-//Sender task
-async fn send(mut stream: FramedStream<UdpStream>, mut rx: mpsc::Receiver<Event>) {
+// Datagram listener task
+async fn listen_datagrams(socket: UdpSocket, tx: mpsc::Sender<Event>, token: CancellationToken) {
+    let mut buf = vec![0; DATAGRAM_BUFFER];
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            Ok((len, peer)) = socket.recv_from(&mut buf) => {
+                process(Bytes::copy_from_slice(&buf[..len]), peer, &tx).instrument(info_span!("connection", protocol = "DATAGRAM", %peer)).await;
+            },
+        }
+    }
+}
+
+/// Runs a new task acting as a UDP sender to a given socket, sending one datagram per `Event`.
+///
+/// The `Event`s whose frame exceeds `MAX_DATAGRAM` are discarded with an `Error::FrameTooLarge`.
+///
+/// # Parameters
+/// - `addr` : the socket address of the listener.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward as UDP datagrams.
+pub async fn new_datagram_sender<T: ToSocketAddrs>(addr: T, rx: mpsc::Receiver<Arc<Event>>) -> Result<()> {
+    let (socket, peer) = connect(addr).await?;
+    tokio::spawn(async move {
+        send_datagrams(socket, rx).await;
+    }.instrument(info_span!("connection", protocol = "DATAGRAM", %peer)));
+    Ok(())
+}
+
+/// Serializes a shared `Event` as a self-contained datagram, reusing its cached frame, failing if it exceeds `MAX_DATAGRAM`.
+pub fn encode_datagram(event: &Arc<Event>) -> Result<Bytes> {
+    let frame = encode_shared(event).map_err(Error::Codec)?;
+    if frame.len() > MAX_DATAGRAM {
+        return Err(Error::FrameTooLarge { size: frame.len(), max: MAX_DATAGRAM });
+    }
+    Ok(frame)
+}
+
+// Datagram sender task
+async fn send_datagrams(socket: UdpSocket, mut rx: mpsc::Receiver<Arc<Event>>) {
     while let Some(event) = rx.recv().await {
-        println!("\x1b[94mOUT\x1b[0m [{}] {} - \"{}\" = {} Bytes", Utc::now(), &event.timestamp, &event.topic, event.data.len());
-        let _ = stream.send(event).await;
+        debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+        let result = match encode_datagram(&event) {
+            Ok(datagram) => socket.send(&datagram).await.map(|_| ()).map_err(Error::Io),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!(error = %e, "send failed");
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,88 @@
+//! This module offers functions to use Unix domain sockets for sending and receiving `Event`s between the processes
+//! of the same device, addressed by the path of the socket.
+//!
+//! The streams are handled as the TCP ones.
+
+use std::{fs, io, os::unix::fs::FileTypeExt, path::Path, sync::Arc, time::Duration};
+
+use tokio::net::{UnixListener, UnixStream};
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use tracing::{Instrument, info_span};
+
+use super::tcp;
+use crate::framing::{EventCodec, FramingConfig, frame_stream_with};
+use crate::{Error, Event, error::Result};
+
+/// Runs a new task acting as a listener on a given socket path.
+///
+/// A socket file left by a terminated listener is replaced, while the one of a running listener makes the bind fail.
+/// The socket file is removed once the listener is cancelled.
+///
+/// # Parameters
+/// - `path` : the path of the socket of the listener.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `tx` : a transmitter to send back the `Event`s received from the Unix streams.
+/// - `idle_timeout` : the time without receiving anything from a peer after which it is considered lost, if any.
+///
+/// # Returns
+/// - cancellation token for handling termination.
+pub async fn new_receiver<P: AsRef<Path>>(path: P, framing: FramingConfig, tx: mpsc::Sender<Event>, idle_timeout: Option<Duration>, token: CancellationToken) -> Result<()> {
+    let path = path.as_ref().to_path_buf();
+    let listener = match UnixListener::bind(&path) {
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse && is_socket(&path) && UnixStream::connect(&path).await.is_err() => {
+            fs::remove_file(&path).map_err(Error::Bind)?;
+            UnixListener::bind(&path)
+        },
+        result => result,
+    }.map_err(Error::Bind)?;
+    tokio::spawn(async move {
+        listen(listener, &path, framing, tx, idle_timeout, token).await;
+        let _ = fs::remove_file(&path);
+    });
+    Ok(())
+}
+
+// Returns `true` if the path is a socket file, `false` otherwise.
+fn is_socket(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket())
+}
+
+// Listener task
+async fn listen(listener: UnixListener, path: &Path, framing: FramingConfig, tx: mpsc::Sender<Event>, idle_timeout: Option<Duration>, token: CancellationToken) {
+    let peer = format!("unix:{}", path.display());
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            Ok((stream, _)) = listener.accept() => {
+                let stream = frame_stream_with(stream, EventCodec::new(framing));
+                let clone = tx.clone();
+                let child = token.child_token();
+                let span = info_span!("connection", protocol = "UNIX", %peer);
+                let peer = peer.clone();
+                tokio::spawn(async move {
+                    tcp::process(stream, peer, clone, idle_timeout, child).await;
+                }.instrument(span));
+            },
+        }
+    }
+}
+
+/// Runs a new task acting as a Unix sender to a given socket path.
+///
+/// # Parameters
+/// - `path` : the path of the socket of the listener.
+/// - `framing` : the serialization format and the compression of the frames.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward to the Unix stream.
+pub async fn new_sender<P: AsRef<Path>>(path: P, framing: FramingConfig, rx: mpsc::Receiver<Arc<Event>>) -> Result<()> {
+    let path = path.as_ref();
+    let stream = UnixStream::connect(path).await.map_err(Error::Connect)?;
+    let codec = EventCodec::new(framing);
+    let stream = frame_stream_with(stream, codec.clone());
+    tokio::spawn(async move {
+        tcp::send(stream, codec, rx).await;
+    }.instrument(info_span!("connection", protocol = "UNIX", peer = %path.display())));
+    Ok(())
+}
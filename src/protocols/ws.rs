@@ -0,0 +1,226 @@
+//! This module offers functions to use the WebSocket protocol for sending and receiving `Event`s, so that browsers
+//! and dashboards can exchange `Event`s with a node without a custom client.
+//!
+//! Each `Event` is carried by a single message: a binary one containing its versioned frame, or a text one containing
+//! its JSON serialization, depending on the `WsMode`. Receivers accept both.
+
+use std::{io, net::SocketAddr, sync::Arc};
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::select;
+use tokio::sync::{broadcast, mpsc};
+use tokio_tungstenite::{WebSocketStream, accept_async, connect_async, tungstenite::Message};
+use tokio_util::sync::CancellationToken;
+
+use tracing::{Instrument, debug, info_span, warn};
+
+use crate::framing::{decode, encode_shared};
+use crate::{Error, Event, HeaderValue, SOURCE_HEADER, error::Result};
+
+/// Encoding of the `Event`s sent over WebSocket.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WsMode {
+    /// Binary messages containing the versioned frame of the `Event`.
+    #[default]
+    Binary,
+    /// Text messages containing the JSON serialization of the `Event`, for clients without a bincode decoder.
+    Json,
+}
+
+/// Serializes a shared `Event` as a WebSocket message, according to the given mode, reusing its cached frame.
+pub fn encode_message(event: &Arc<Event>, mode: WsMode) -> Result<Message> {
+    match mode {
+        WsMode::Binary => Ok(Message::Binary(encode_shared(event).map_err(Error::Codec)?)),
+        WsMode::Json => {
+            let json = serde_json::to_string(event.as_ref()).map_err(|e| Error::Codec(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+            Ok(Message::text(json))
+        },
+    }
+}
+
+/// Deserializes an `Event` from a binary or text WebSocket message, returning `None` for the control messages.
+pub fn decode_message(message: &Message) -> Option<Result<Event>> {
+    match message {
+        Message::Binary(frame) => Some(decode(frame).map_err(Error::Codec)),
+        Message::Text(json) => Some(serde_json::from_str::<Event>(json.as_str())
+            .map(|event| Event { hops: event.hops.saturating_add(1), ..event })
+            .map_err(|e| Error::Codec(io::Error::new(io::ErrorKind::InvalidData, e)))),
+        _ => None,
+    }
+}
+
+/// Runs a new task acting as a WebSocket server on a given socket, receiving the `Event`s sent by its clients.
+///
+/// # Parameters
+/// - `addr` : the socket address of the server.
+/// - `tx` : a transmitter to send back the `Event`s received from the WebSocket connections.
+///
+/// # Returns
+/// - cancellation token for handling termination.
+pub async fn new_receiver<T: ToSocketAddrs>(addr: T, tx: mpsc::Sender<Event>, token: CancellationToken) -> Result<()> {
+    let listener = TcpListener::bind(addr).await.map_err(Error::Bind)?;
+    tokio::spawn(async move {
+        listen(listener, tx, token).await;
+    });
+    Ok(())
+}
+
+// Listener task
+async fn listen(listener: TcpListener, tx: mpsc::Sender<Event>, token: CancellationToken) {
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            Ok((stream, peer)) = listener.accept() => {
+                let clone = tx.clone();
+                let child = token.child_token();
+                tokio::spawn(async move {
+                    match accept_async(stream).await {
+                        Ok(ws) => process(ws, peer, clone, child).await,
+                        Err(e) => warn!(error = %e, "handshake failed"),
+                    }
+                }.instrument(info_span!("connection", protocol = "WS", %peer)));
+            },
+        }
+    }
+}
+
+// Connection handler
+async fn process<S: AsyncRead + AsyncWrite + Unpin>(mut ws: WebSocketStream<S>, peer: SocketAddr, tx: mpsc::Sender<Event>, token: CancellationToken) {
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            message = ws.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    Some(Err(e)) => {
+                        warn!(error = %e, "connection lost");
+                        break;
+                    },
+                    None => break,
+                };
+                match decode_message(&message) {
+                    Some(Ok(mut event)) => {
+                        event.headers.insert(String::from(SOURCE_HEADER), HeaderValue::from(peer.to_string()));
+                        debug!(kind = "IN", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "received");
+                        let _ = tx.send(event).await;
+                    },
+                    Some(Err(e)) => warn!(error = %e, "invalid message"),
+                    None => {},
+                }
+            },
+        }
+    }
+}
+
+/// Runs a new task acting as a WebSocket client of a given server, sending it the `Event`s.
+///
+/// # Parameters
+/// - `url` : the URL of the server, e.g. `ws://127.0.0.1:8080`.
+/// - `mode` : the encoding of the `Event`s.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward to the WebSocket connection.
+pub async fn new_sender(url: &str, mode: WsMode, rx: mpsc::Receiver<Arc<Event>>) -> Result<()> {
+    let (ws, _) = connect_async(url).await.map_err(|e| Error::Connect(io::Error::new(io::ErrorKind::ConnectionRefused, e)))?;
+    let span = info_span!("connection", protocol = "WS", peer = %url);
+    tokio::spawn(async move {
+        send(ws, mode, rx).await;
+    }.instrument(span));
+    Ok(())
+}
+
+// Sender task
+async fn send(mut ws: WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>, mode: WsMode, mut rx: mpsc::Receiver<Arc<Event>>) {
+    while let Some(event) = rx.recv().await {
+        debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+        let result = match encode_message(&event, mode) {
+            Ok(message) => ws.send(message).await.map_err(|e| Error::Io(io::Error::other(e))),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!(error = %e, "send failed");
+        }
+    }
+    let _ = ws.close(None).await;
+}
+
+/// Runs a new task acting as a WebSocket server on a given socket, sending the `Event`s to all its clients,
+/// e.g. the dashboards following the progress of a training.
+///
+/// The `Event`s are encoded once for all the clients, and the ones a slow client cannot keep up with are skipped.
+///
+/// # Parameters
+/// - `addr` : the socket address of the server.
+/// - `mode` : the encoding of the `Event`s.
+/// - `rx` : a receiver to use as the source of the `Event`s to forward to the WebSocket connections.
+/// - `buffer` : the number of `Event`s buffered for each client.
+/// - `token` : cancellation token for handling termination.
+pub async fn new_publisher<T: ToSocketAddrs>(addr: T, mode: WsMode, rx: mpsc::Receiver<Arc<Event>>, buffer: usize, token: CancellationToken) -> Result<()> {
+    let listener = TcpListener::bind(addr).await.map_err(Error::Bind)?;
+    tokio::spawn(async move {
+        publish(listener, mode, rx, buffer, token).await;
+    });
+    Ok(())
+}
+
+// Publisher task
+async fn publish(listener: TcpListener, mode: WsMode, mut rx: mpsc::Receiver<Arc<Event>>, buffer: usize, token: CancellationToken) {
+    let (clients, _) = broadcast::channel(buffer.max(1));
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            Ok((stream, peer)) = listener.accept() => {
+                let messages = clients.subscribe();
+                let child = token.child_token();
+                tokio::spawn(async move {
+                    match accept_async(stream).await {
+                        Ok(ws) => serve(ws, messages, child).await,
+                        Err(e) => warn!(error = %e, "handshake failed"),
+                    }
+                }.instrument(info_span!("connection", protocol = "WS", %peer)));
+            },
+            event = rx.recv() => {
+                let event = match event {
+                    Some(event) => event,
+                    None => break,
+                };
+                debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+                match encode_message(&event, mode) {
+                    Ok(message) => {
+                        let _ = clients.send(message);
+                    },
+                    Err(e) => warn!(error = %e, "send failed"),
+                }
+            },
+        }
+    }
+}
+
+// Client handler of a publisher, which ignores the messages of the client.
+async fn serve(mut ws: WebSocketStream<TcpStream>, mut messages: broadcast::Receiver<Message>, token: CancellationToken) {
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            message = messages.recv() => {
+                match message {
+                    Ok(message) => {
+                        if let Err(e) = ws.send(message).await {
+                            warn!(error = %e, "connection lost");
+                            return;
+                        }
+                    },
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => warn!(skipped, "client lagging, events skipped"),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            },
+            message = ws.next() => {
+                if !matches!(message, Some(Ok(_))) {
+                    return;
+                }
+            },
+        }
+    }
+    let _ = ws.close(None).await;
+}
@@ -0,0 +1,175 @@
+//! This module offers the propagation of the `Subscription`s between the two peers of a connection, called a link.
+//!
+//! Each side advertises to the other the `Interest`s of its local `Subscription`s, as they come and go, with control
+//! `Event`s on the `SUBSCRIBE_TOPIC` and `UNSUBSCRIBE_TOPIC`, and transmits only the `Event`s matching the `Interest`s
+//! advertised by the other side. Only the advertised `Subscription`s are propagated, hence not the ones forwarding
+//! `Event`s to other peers, so the `Interest`s of a node are known only by its direct peers.
+
+use std::{collections::BTreeMap, io, time::Duration};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use regex::Regex;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::time::{Instant, sleep_until};
+use tokio_util::sync::CancellationToken;
+
+use tracing::{debug, warn};
+
+use crate::config::InterestSpec;
+use crate::framing::{EventCodec, FramedStream, accept_compression, is_negotiation, is_too_large};
+use crate::protocols::heartbeat::{HEARTBEAT_INTERVAL, connection_lost_event, expired, heartbeat_event, is_heartbeat};
+use crate::{Backpressure, Command, Error, Event, HeaderValue, Interest, SOURCE_HEADER, Subscription, SubscriptionChange, SubscriptionId, error::Result};
+
+/// Topic of the control `Event`s advertising a `Subscription`, or the new `Interest` of an advertised one,
+/// containing its `Interest` serialized as a JSON `InterestSpec`.
+pub const SUBSCRIBE_TOPIC: &str = "$subscribe";
+/// Topic of the control `Event`s withdrawing an advertised `Subscription`.
+pub const UNSUBSCRIBE_TOPIC: &str = "$unsubscribe";
+/// Header of the control `Event`s containing the id of the `Subscription` on the advertising peer.
+pub const SUBSCRIPTION_HEADER: &str = "subscription";
+
+/// Builds the control `Event` advertising a `Subscription` with the given `Interest`.
+pub fn subscribe_event(id: SubscriptionId, interest: &Interest) -> Result<Event> {
+    let data = serde_json::to_vec(&InterestSpec::from(interest)).map_err(|e| Error::Codec(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    Ok(Event::new(SUBSCRIBE_TOPIC, Bytes::from(data)).with_header(SUBSCRIPTION_HEADER, id.to_string()))
+}
+
+/// Builds the control `Event` withdrawing an advertised `Subscription`.
+pub fn unsubscribe_event(id: SubscriptionId) -> Event {
+    Event::new(UNSUBSCRIBE_TOPIC, Bytes::new()).with_header(SUBSCRIPTION_HEADER, id.to_string())
+}
+
+/// Returns `true` if the `Event` is a control one of the propagation of the `Subscription`s, `false` otherwise.
+pub fn is_control(event: &Event) -> bool {
+    event.topic == SUBSCRIBE_TOPIC || event.topic == UNSUBSCRIBE_TOPIC
+}
+
+// Returns the interest excluding the events received from the given peer, so that they are not sent back to it.
+pub(crate) fn not_from(peer: &str) -> Interest {
+    let source = Regex::new(&format!("^{}$", regex::escape(peer))).expect("escaped pattern");
+    !Interest::Source(source)
+}
+
+// Applies a control event of the peer to the interests it advertised, keyed by the id of their subscriptions.
+fn update(interests: &mut BTreeMap<String, Interest>, event: &Event) -> Result<()> {
+    let id = event.header(SUBSCRIPTION_HEADER).and_then(HeaderValue::as_str)
+        .ok_or_else(|| Error::Codec(io::Error::new(io::ErrorKind::InvalidData, "missing subscription header")))?;
+    if event.topic == UNSUBSCRIBE_TOPIC {
+        interests.remove(id);
+        return Ok(());
+    }
+    let spec: InterestSpec = serde_json::from_slice(&event.data).map_err(|e| Error::Codec(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    interests.insert(id.to_string(), spec.build()?);
+    Ok(())
+}
+
+// Link handler, shared by both the sides of the connection, which offers to the peer the compression of the codec, if any.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn link<S: AsyncRead + AsyncWrite + Unpin>(stream: FramedStream<S>, codec: EventCodec, peer: String, allowed: Interest, tx: mpsc::Sender<Event>, dispatcher: mpsc::Sender<Command>, buffer: usize, idle_timeout: Option<Duration>, token: CancellationToken) {
+    let (mut sink, mut stream) = stream.split();
+    if let Some(offer) = codec.offer() {
+        if let Err(e) = sink.send(offer.into()).await {
+            return warn!(error = %e, "connection lost");
+        }
+    }
+    let (watcher, mut changes) = mpsc::unbounded_channel();
+    let (sub, mut inbox) = Subscription::new(Interest::Any(Vec::new()), buffer, Backpressure::DropNewest);
+    let handle = sub.handle(dispatcher.clone());
+    if dispatcher.send(Command::Subscribe(sub.advertised(false))).await.is_err() || dispatcher.send(Command::Watch(watcher)).await.is_err() {
+        return warn!("dispatcher closed");
+    }
+    let own = not_from(&peer);
+    let mut interests = BTreeMap::new();
+    let (mut sent, mut last) = (Instant::now(), Instant::now());
+    loop {
+        select! {
+            _ = token.cancelled() => break,
+            _ = expired(last, idle_timeout) => {
+                warn!("connection lost");
+                let _ = tx.send(connection_lost_event(&peer)).await;
+                break;
+            },
+            _ = sleep_until(sent + HEARTBEAT_INTERVAL) => {
+                sent = Instant::now();
+                if let Err(e) = sink.send(heartbeat_event().into()).await {
+                    warn!(error = %e, "connection lost");
+                    break;
+                }
+            },
+            change = changes.recv() => {
+                let control = match change {
+                    Some(SubscriptionChange::Subscribed(id, interest)) => subscribe_event(id, &interest),
+                    Some(SubscriptionChange::Unsubscribed(id)) => Ok(unsubscribe_event(id)),
+                    None => break,
+                };
+                let result = match control {
+                    Ok(event) => {
+                        sent = Instant::now();
+                        sink.send(event.into()).await
+                    },
+                    Err(e) => {
+                        warn!(error = %e, "invalid interest");
+                        continue;
+                    },
+                };
+                if let Err(e) = result {
+                    warn!(error = %e, "connection lost");
+                    break;
+                }
+            },
+            Some(event) = inbox.recv() => {
+                sent = Instant::now();
+                debug!(kind = "OUT", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "sent");
+                if let Err(e) = sink.send(event).await {
+                    if is_too_large(&e) {
+                        warn!(error = %e, "event dropped");
+                        continue;
+                    }
+                    warn!(error = %e, "connection lost");
+                    break;
+                }
+            },
+            msg = stream.next() => {
+                last = Instant::now();
+                match msg {
+                    Some(Ok(event)) if is_heartbeat(&event) => {},
+                    Some(Ok(event)) if is_negotiation(&event) => {
+                        codec.accept(&event);
+                        if let Some(reply) = accept_compression(&event) {
+                            if let Err(e) = sink.send(reply.into()).await {
+                                warn!(error = %e, "connection lost");
+                                break;
+                            }
+                        }
+                    },
+                    Some(Ok(event)) if is_control(&event) => {
+                        if let Err(e) = update(&mut interests, &event) {
+                            warn!(error = %e, "invalid control event");
+                            continue;
+                        }
+                        debug!(topic = %event.topic, interests = interests.len(), "remote interests updated");
+                        let wanted = Interest::Any(interests.values().cloned().collect());
+                        if handle.set_interest(Interest::All(vec![wanted, allowed.clone(), own.clone()])).await.is_err() {
+                            break;
+                        }
+                    },
+                    Some(Ok(mut event)) => {
+                        event.headers.insert(String::from(SOURCE_HEADER), HeaderValue::from(peer.clone()));
+                        debug!(kind = "IN", topic = %event.topic, timestamp = %event.timestamp, size = event.data.len(), latency_ms = event.age().num_milliseconds(), "received");
+                        let _ = tx.send(event).await;
+                    },
+                    Some(Err(e)) if is_too_large(&e) => {
+                        warn!(error = %e, "frame rejected");
+                        break;
+                    },
+                    Some(Err(e)) => warn!(error = %e, "invalid frame"),
+                    None => break,
+                }
+            },
+        }
+    }
+    let _ = handle.cancel().await;
+}
@@ -0,0 +1,50 @@
+//! This module offers a request/reply primitive built on top of the `Dispatcher`.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::{sync::mpsc, time::Instant};
+
+use super::{Backpressure, Command, Error, Event, Interest, Subscription, error::Result, random_u64};
+
+/// Forwards an `Event` with a new `correlation_id` to the `Dispatcher`, and waits for the replies carrying the same id.
+/// 
+/// # Parameters
+/// - `dispatcher` : represents the sender linked to the desired `Dispatcher`.
+/// - `event` : the request, whose `correlation_id` is overwritten.
+/// - `reply_interest` : represents the validation criteria of the replies, that must also carry the `correlation_id` of the request.
+/// - `n` : the number of replies to wait for.
+/// - `timeout` : the maximum time to wait for all the replies.
+/// 
+/// # Returns
+/// - The `n` replies, in order of arrival, wrapped in a `Result`.
+/// - `Error::Timeout` with the replies received so far, if the timeout expires.
+pub async fn request(dispatcher: &mpsc::Sender<Command>, mut event: Event, reply_interest: Interest, n: usize, timeout: Duration) -> Result<Vec<Arc<Event>>> {
+    let deadline = Instant::now() + timeout;
+    let id = correlation_id();
+    event.correlation_id = Some(id);
+    // Only the replies to this request are buffered, so the `Dispatcher` is blocked only while they are consumed.
+    let interest = Interest::All(vec![reply_interest, Interest::Correlation(id)]);
+    let (handle, mut rx) = Subscription::subscribe(interest, n.max(1), Backpressure::Block(timeout), dispatcher.clone()).await?;
+    dispatcher.send(Command::Forward(event)).await?;
+
+    let mut replies = Vec::with_capacity(n);
+    let result = loop {
+        if replies.len() == n {
+            break Ok(replies);
+        }
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(reply)) => replies.push(reply),
+            Ok(None) => break Err(Error::DispatcherClosed),
+            Err(_) => break Err(Error::Timeout(replies)),
+        }
+    };
+    // Closing the channel first prevents the `Dispatcher` from blocking on late replies.
+    drop(rx);
+    let _ = handle.cancel().await;
+    result
+}
+
+// Returns a new random correlation id, unlikely to collide with the ones generated by other nodes.
+fn correlation_id() -> u64 {
+    random_u64()
+}
@@ -1,5 +1,7 @@
 use std::fs;
 
+use futures::StreamExt;
+
 use tokio::{self, sync::mpsc::Sender};
 
 use crate::{
@@ -27,13 +29,208 @@ async fn local_run() {
 
 async fn local_process(tx: Sender<Command>) {
     let interest = Interest::new(Regex::new(r"^test[0-9]$").unwrap());
-    let mut rx = Subscription::subscribe(interest, 32, tx.clone()).await.unwrap();
+    let (_, mut rx) = Subscription::subscribe(interest, 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
     let event = Event::new("test0", Bytes::from_static("success".as_bytes()));
     tx.send(Command::Forward(event)).await.unwrap();
     let msg = rx.recv().await.unwrap();
     assert!(msg.as_ref().data.to_vec().ends_with("success".as_bytes()));
 }
 
+#[test]
+fn handle() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            handle_run().await;
+        });
+}
+
+async fn handle_run() {
+    let token = CancellationToken::new();
+    let tx = Dispatcher::new(32, token.clone());
+    let interest = Interest::new(Regex::new(r"^handle$").unwrap());
+    let (handle, mut rx) = Subscription::subscribe(interest, 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
+
+    handle.pause().await.unwrap();
+    tx.send(Command::Forward(Event::new("handle", Bytes::from_static("paused".as_bytes())))).await.unwrap();
+    handle.resume().await.unwrap();
+    tx.send(Command::Forward(Event::new("handle", Bytes::from_static("resumed".as_bytes())))).await.unwrap();
+    let msg = rx.recv().await.unwrap();
+    assert!(msg.as_ref().data.to_vec().ends_with("resumed".as_bytes()), "{:?}", msg.as_ref().data);
+
+    handle.cancel().await.unwrap();
+    assert!(rx.recv().await.is_none());
+
+    token.cancel();
+}
+
+#[test]
+fn index() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            index_run().await;
+        });
+}
+
+async fn index_run() {
+    let token = CancellationToken::new();
+    let tx = Dispatcher::new(32, token.clone());
+    let (_, mut exact_rx) = Subscription::subscribe(Interest::exact("a/b"), 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
+    let (_, mut single_rx) = Subscription::subscribe(Interest::wildcard("a/+"), 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
+    let (_, mut multi_rx) = Subscription::subscribe(Interest::wildcard("a/#"), 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
+    let (_, mut regex_rx) = Subscription::subscribe(Interest::new(Regex::new(r"^a/b/c+$").unwrap()), 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
+
+    let (_, mut barrier_rx) = Subscription::subscribe(Interest::exact("barrier"), 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
+
+    for topic in ["a", "a/b", "a/b/ccc", "b/a"] {
+        tx.send(Command::Forward(Event::new(topic, Bytes::new()))).await.unwrap();
+    }
+    // Events are delivered in order, so every previous event is already buffered once the barrier is received.
+    tx.send(Command::Forward(Event::new("barrier", Bytes::new()))).await.unwrap();
+    barrier_rx.recv().await.unwrap();
+
+    let mut topics = Vec::new();
+    for rx in [&mut exact_rx, &mut single_rx, &mut multi_rx, &mut regex_rx] {
+        let mut received = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            received.push(event.topic.clone());
+        }
+        topics.push(received);
+    }
+    assert_eq!(topics[0], vec!["a/b"]);
+    assert_eq!(topics[1], vec!["a/b"]);
+    assert_eq!(topics[2], vec!["a", "a/b", "a/b/ccc"]);
+    assert_eq!(topics[3], vec!["a/b/ccc"]);
+
+    token.cancel();
+}
+
+#[test]
+fn backpressure() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            backpressure_run().await;
+        });
+}
+
+async fn backpressure_run() {
+    let token = CancellationToken::new();
+    let tx = Dispatcher::new(32, token.clone());
+    let (newest, mut newest_rx) = Subscription::subscribe(Interest::exact("bp"), 2, Backpressure::DropNewest, tx.clone()).await.unwrap();
+    let (oldest, mut oldest_rx) = Subscription::subscribe(Interest::exact("bp"), 2, Backpressure::DropOldest, tx.clone()).await.unwrap();
+    let (disconnect, mut disconnect_rx) = Subscription::subscribe(Interest::exact("bp"), 2, Backpressure::Disconnect, tx.clone()).await.unwrap();
+    let (_, mut barrier_rx) = Subscription::subscribe(Interest::exact("barrier"), 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
+
+    for data in ["1", "2", "3", "4"] {
+        tx.send(Command::Forward(Event::new("bp", Bytes::from(data)))).await.unwrap();
+    }
+    tx.send(Command::Forward(Event::new("barrier", Bytes::new()))).await.unwrap();
+    barrier_rx.recv().await.unwrap();
+
+    let mut received = Vec::new();
+    for rx in [&mut newest_rx, &mut oldest_rx, &mut disconnect_rx] {
+        let mut data = Vec::new();
+        while let Some(event) = rx.recv().await {
+            data.push(event.data.clone());
+            if data.len() == 2 {
+                break;
+            }
+        }
+        received.push(data);
+    }
+    assert_eq!(received[0], vec!["1", "2"]);
+    assert_eq!(received[1], vec!["3", "4"]);
+    assert_eq!(received[2], vec!["1", "2"]);
+    assert!(disconnect_rx.recv().await.is_none());
+    assert_eq!((newest.dropped(), oldest.dropped(), disconnect.dropped()), (2, 2, 1));
+
+    token.cancel();
+}
+
+#[test]
+fn backpressure_block() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            backpressure_block_run().await;
+        });
+}
+
+async fn backpressure_block_run() {
+    let token = CancellationToken::new();
+    let tx = Dispatcher::new(32, token.clone());
+    let (block, mut block_rx) = Subscription::subscribe(Interest::exact("bp"), 1, Backpressure::Block(Duration::from_millis(300)), tx.clone()).await.unwrap();
+    let (_, mut barrier_rx) = Subscription::subscribe(Interest::exact("barrier"), 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
+
+    // The full subscriber blocks the delivery until there is room again.
+    for data in ["1", "2"] {
+        tx.send(Command::Forward(Event::new("bp", Bytes::from(data)))).await.unwrap();
+    }
+    tx.send(Command::Forward(Event::new("barrier", Bytes::new()))).await.unwrap();
+    assert!(tokio::time::timeout(Duration::from_millis(100), barrier_rx.recv()).await.is_err());
+    assert_eq!(block_rx.recv().await.unwrap().data, "1");
+    tokio::time::timeout(Duration::from_millis(100), barrier_rx.recv()).await.unwrap().unwrap();
+    assert_eq!(block_rx.recv().await.unwrap().data, "2");
+    assert_eq!(block.dropped(), 0);
+
+    // The blocked `Event` is dropped once the timeout expires.
+    let start = std::time::Instant::now();
+    for data in ["3", "4"] {
+        tx.send(Command::Forward(Event::new("bp", Bytes::from(data)))).await.unwrap();
+    }
+    tx.send(Command::Forward(Event::new("barrier", Bytes::new()))).await.unwrap();
+    barrier_rx.recv().await.unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(300), "{:?}", start.elapsed());
+    assert_eq!(block_rx.recv().await.unwrap().data, "3");
+    assert!(block_rx.try_recv().is_err());
+    assert_eq!(block.dropped(), 1);
+
+    token.cancel();
+}
+
+#[test]
+fn request_reply() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            request_reply_run().await;
+        });
+}
+
+async fn request_reply_run() {
+    let token = CancellationToken::new();
+    let tx = Dispatcher::new(32, token.clone());
+    let (_, mut rx) = Subscription::subscribe(Interest::exact("ping"), 32, Backpressure::DropNewest, tx.clone()).await.unwrap();
+    let clone = tx.clone();
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            clone.send(Command::Forward(Event::new("pong", Bytes::from_static("unrelated".as_bytes())))).await.unwrap();
+            clone.send(Command::Forward(event.reply("pong", event.data.clone()))).await.unwrap();
+        }
+    });
+
+    let replies = request(&tx, Event::new("ping", Bytes::from_static("success".as_bytes())), Interest::exact("pong"), 1, Duration::from_secs(1)).await.unwrap();
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].data.to_vec().ends_with("success".as_bytes()), "{:?}", replies[0].data);
+
+    let result = request(&tx, Event::new("ping", Bytes::new()), Interest::exact("pong"), 2, Duration::from_millis(100)).await;
+    assert!(matches!(result, Err(Error::Timeout(replies)) if replies.len() == 1));
+
+    token.cancel();
+}
+
 #[test]
 fn remote_tcp() {
     tokio::runtime::Builder::new_multi_thread()
@@ -53,7 +250,7 @@ async fn remote_tcp_run() {
 async fn remote_tcp_server_process() {
     let (tx, mut rx) = mpsc::channel(32);
     let token = CancellationToken::new();
-    tcp::new_receiver("127.0.0.1:8080", tx, token.clone()).await.unwrap();
+    tcp::new_receiver("127.0.0.1:8080", Default::default(), tx, None, token.clone()).await.unwrap();
     let event = rx.recv().await.unwrap();
     assert!(event.data.to_vec().ends_with("success".as_bytes()));
     token.cancel()
@@ -61,9 +258,45 @@ async fn remote_tcp_server_process() {
 
 async fn remote_tcp_client_process() {
     let (tx, rx) = mpsc::channel(32);
-    tcp::new_sender("127.0.0.1:8080", rx).await.unwrap();
+    tcp::new_sender("127.0.0.1:8080", Default::default(), rx).await.unwrap();
     let event = Event::new("test0", Bytes::from_static("success".as_bytes()));
-    tx.send(event).await.unwrap();
+    tx.send(event.into()).await.unwrap();
+}
+
+#[test]
+fn reconnect_tcp() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            reconnect_tcp_run().await;
+        });
+}
+
+async fn reconnect_tcp_run() {
+    let token = CancellationToken::new();
+    let backoff = tcp::Backoff {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(50),
+        ..Default::default()
+    };
+    let (tx, rx) = mpsc::channel(32);
+    let mut state = tcp::new_reconnecting_sender("127.0.0.1:8090", Default::default(), rx, backoff, 32, token.clone());
+    tx.send(Event::new("test0", Bytes::from_static("buffered".as_bytes())).into()).await.unwrap();
+    state.wait_for(|state| *state == tcp::ConnectionState::Disconnected).await.unwrap();
+
+    let (r_tx, mut r_rx) = mpsc::channel(32);
+    tcp::new_receiver("127.0.0.1:8090", Default::default(), r_tx, None, token.clone()).await.unwrap();
+    state.wait_for(|state| matches!(state, tcp::ConnectionState::Connected(_))).await.unwrap();
+    tx.send(Event::new("test0", Bytes::from_static("connected".as_bytes())).into()).await.unwrap();
+
+    let event = r_rx.recv().await.unwrap();
+    assert!(event.data.to_vec().ends_with("buffered".as_bytes()), "{:?}", event.data);
+    let event = r_rx.recv().await.unwrap();
+    assert!(event.data.to_vec().ends_with("connected".as_bytes()), "{:?}", event.data);
+
+    token.cancel();
 }
 
 #[test]
@@ -78,24 +311,220 @@ fn remote_udp() {
 }
 
 async fn remote_udp_run() {
-    tokio::spawn(remote_udp_server_process());
-    tokio::spawn(remote_udp_client_process());
+    let token = CancellationToken::new();
+    let (r_tx, mut r_rx) = mpsc::channel(32);
+    udp::new_receiver("127.0.0.1:8081", r_tx, None, token.clone()).await.unwrap();
+
+    let (tx, rx) = mpsc::channel(32);
+    udp::new_sender("127.0.0.1:8081", rx).await.unwrap();
+    let model = Bytes::from((0..2 * 1024 * 1024).map(|i| i as u8).collect::<Vec<u8>>());
+    tx.send(Event::new("model", model.clone()).into()).await.unwrap();
+    tx.send(Event::new("test0", Bytes::from_static("success".as_bytes())).into()).await.unwrap();
+
+    let event = r_rx.recv().await.unwrap();
+    assert_eq!(event.topic, "model");
+    assert_eq!(event.data, model);
+    let event = r_rx.recv().await.unwrap();
+    assert!(event.data.to_vec().ends_with("success".as_bytes()));
+
+    token.cancel();
 }
 
-async fn remote_udp_server_process() {
-    let (tx, mut rx) = mpsc::channel(32);
+#[test]
+fn datagram_udp() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            datagram_udp_run().await;
+        });
+}
+
+async fn datagram_udp_run() {
     let token = CancellationToken::new();
-    udp::new_receiver("127.0.0.1:8081", tx, token.clone()).await.unwrap();
-    let event = rx.recv().await.unwrap();
+    let (r_tx, mut r_rx) = mpsc::channel(32);
+    udp::new_datagram_receiver("127.0.0.1:8082", r_tx, token.clone()).await.unwrap();
+
+    let oversize = Arc::new(Event::new("test0", Bytes::from(vec![0; udp::MAX_DATAGRAM])));
+    assert!(matches!(udp::encode_datagram(&oversize), Err(Error::FrameTooLarge { .. })));
+
+    let (tx, rx) = mpsc::channel(32);
+    udp::new_datagram_sender("127.0.0.1:8082", rx).await.unwrap();
+    tx.send(oversize).await.unwrap();
+    tx.send(Event::new("test0", Bytes::from_static("success".as_bytes())).into()).await.unwrap();
+
+    let event = r_rx.recv().await.unwrap();
     assert!(event.data.to_vec().ends_with("success".as_bytes()));
-    token.cancel()
+    assert!(event.header(SOURCE_HEADER).is_some());
+
+    token.cancel();
+}
+
+#[test]
+fn remote_tls() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            remote_tls_run().await;
+        });
+}
+
+// Writes self-signed certificates for a server and a client in the given directory, each trusting the other.
+fn self_signed(dir: &std::path::Path) -> (tls::TlsConfig, tls::TlsConfig) {
+    fs::create_dir_all(dir).unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    for (name, subject) in [("server", "127.0.0.1"), ("client", "client")] {
+        let certified = rcgen::generate_simple_self_signed(vec![subject.to_string()]).unwrap();
+        fs::write(path(&format!("{}.pem", name)), certified.cert.pem()).unwrap();
+        fs::write(path(&format!("{}.key", name)), certified.key_pair.serialize_pem()).unwrap();
+    }
+    let server = tls::TlsConfig {
+        cert: Some(path("server.pem")),
+        key: Some(path("server.key")),
+        ca: Some(path("client.pem")),
+        server_name: None,
+    };
+    let client = tls::TlsConfig {
+        cert: Some(path("client.pem")),
+        key: Some(path("client.key")),
+        ca: Some(path("server.pem")),
+        server_name: None,
+    };
+    (server, client)
+}
+
+async fn remote_tls_run() {
+    let dir = std::env::temp_dir().join("commnode-test-tls");
+    let (server, client) = self_signed(&dir);
+
+    let token = CancellationToken::new();
+    let (r_tx, mut r_rx) = mpsc::channel(32);
+    tls::new_receiver("127.0.0.1:8083", server.server_config().unwrap(), Default::default(), r_tx, None, token.clone()).await.unwrap();
+
+    let (tx, rx) = mpsc::channel(32);
+    tls::new_sender("127.0.0.1:8083", client.server_name("127.0.0.1:8083").unwrap(), client.client_config().unwrap(), Default::default(), rx).await.unwrap();
+    tx.send(Event::new("test0", Bytes::from_static("success".as_bytes())).into()).await.unwrap();
+
+    let event = r_rx.recv().await.unwrap();
+    assert!(event.data.to_vec().ends_with("success".as_bytes()));
+
+    let anonymous = tls::TlsConfig { cert: None, key: None, ..client };
+    let (tx, rx) = mpsc::channel(32);
+    tls::new_sender("127.0.0.1:8083", anonymous.server_name("127.0.0.1:8083").unwrap(), anonymous.client_config().unwrap(), Default::default(), rx).await.unwrap();
+    tx.send(Event::new("test0", Bytes::from_static("rejected".as_bytes())).into()).await.unwrap();
+    assert!(tokio::time::timeout(Duration::from_millis(200), r_rx.recv()).await.is_err());
+
+    token.cancel();
+    fs::remove_dir_all(dir).unwrap();
 }
 
-async fn remote_udp_client_process() {
+#[test]
+fn remote_quic() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            remote_quic_run().await;
+        });
+}
+
+async fn remote_quic_run() {
+    let dir = std::env::temp_dir().join("commnode-test-quic");
+    let (server, client) = self_signed(&dir);
+
+    let token = CancellationToken::new();
+    let (r_tx, mut r_rx) = mpsc::channel(32);
+    quic::new_receiver("127.0.0.1:8086", server.server_config().unwrap(), r_tx, token.clone()).await.unwrap();
+
     let (tx, rx) = mpsc::channel(32);
-    udp::new_sender("127.0.0.1:8081", rx).await.unwrap();
-    let event = Event::new("test0", Bytes::from_static("success".as_bytes()));
-    tx.send(event).await.unwrap();
+    quic::new_sender("127.0.0.1:8086", "127.0.0.1", client.client_config().unwrap(), rx).await.unwrap();
+    let model = Bytes::from(vec![7; 16 * 1024 * 1024]);
+    tx.send(Event::new("model", model.clone()).into()).await.unwrap();
+    tx.send(Event::new("control", Bytes::from_static("stop".as_bytes())).into()).await.unwrap();
+
+    // The model and the control event travel on different streams, hence in any order.
+    let mut received = HashMap::new();
+    for _ in 0..2 {
+        let event = r_rx.recv().await.unwrap();
+        received.insert(event.topic.clone(), event.data);
+    }
+    assert_eq!(received.get("model"), Some(&model));
+    assert_eq!(received.get("control").map(|data| &data[..]), Some("stop".as_bytes()));
+
+    token.cancel();
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn remote_unix() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            remote_unix_run().await;
+        });
+}
+
+#[cfg(unix)]
+async fn remote_unix_run() {
+    let path = std::env::temp_dir().join("commnode-test.sock");
+    let _ = fs::remove_file(&path);
+    // A socket file left by a terminated listener does not prevent the bind.
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+    let token = CancellationToken::new();
+    let (r_tx, mut r_rx) = mpsc::channel(32);
+    unix::new_receiver(&path, Default::default(), r_tx, None, token.clone()).await.unwrap();
+    assert!(unix::new_receiver(&path, Default::default(), mpsc::channel(32).0, None, token.clone()).await.is_err());
+
+    let (tx, rx) = mpsc::channel(32);
+    unix::new_sender(&path, Default::default(), rx).await.unwrap();
+    tx.send(Event::new("test0", Bytes::from_static("success".as_bytes())).into()).await.unwrap();
+
+    let event = r_rx.recv().await.unwrap();
+    assert!(event.data.to_vec().ends_with("success".as_bytes()));
+    assert!(event.header(SOURCE_HEADER).and_then(HeaderValue::as_str).is_some_and(|source| source.starts_with("unix:")));
+
+    token.cancel();
+}
+
+#[test]
+fn remote_ws() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            remote_ws_run().await;
+        });
+}
+
+async fn remote_ws_run() {
+    let token = CancellationToken::new();
+    let (r_tx, mut r_rx) = mpsc::channel(32);
+    ws::new_receiver("127.0.0.1:8084", r_tx, token.clone()).await.unwrap();
+    let (tx, rx) = mpsc::channel(32);
+    ws::new_sender("ws://127.0.0.1:8084", ws::WsMode::Binary, rx).await.unwrap();
+    tx.send(Event::new("test0", Bytes::from_static("success".as_bytes())).into()).await.unwrap();
+    let event = r_rx.recv().await.unwrap();
+    assert!(event.data.to_vec().ends_with("success".as_bytes()));
+
+    let (tx, rx) = mpsc::channel(32);
+    ws::new_publisher("127.0.0.1:8085", ws::WsMode::Json, rx, 32, token.clone()).await.unwrap();
+    let (mut dashboard, _) = tokio_tungstenite::connect_async("ws://127.0.0.1:8085").await.unwrap();
+    tx.send(Event::new("progress", Bytes::from_static("epoch 1".as_bytes())).into()).await.unwrap();
+    let message = dashboard.next().await.unwrap().unwrap();
+    let json: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+    assert_eq!(json["topic"], "progress");
+    assert_eq!(ws::decode_message(&message).unwrap().unwrap().data, Bytes::from_static("epoch 1".as_bytes()));
+
+    token.cancel();
 }
 
 #[test]
@@ -119,12 +548,28 @@ async fn config_run() {
                     Channel {
                         address: "127.0.0.1:8000".to_string(),
                         protocol: Protocol::TCP,
-                        interest: r"^TCP$".to_string(),
+                        interest: r"^TCP$".into(),
+                        tls: None,
+                        ws_mode: None,
+                        duplex: None,
+                        propagate: false,
+                        idle_timeout_ms: None,
+                        compression: None,
+                        format: None,
+                        max_frame_length: None,
                     },
                     Channel {
                         address: "127.0.0.1:8001".to_string(),
                         protocol: Protocol::UDP,
-                        interest: r"^UDP$".to_string(),
+                        interest: r"^UDP$".into(),
+                        tls: None,
+                        ws_mode: None,
+                        duplex: None,
+                        propagate: false,
+                        idle_timeout_ms: None,
+                        compression: None,
+                        format: None,
+                        max_frame_length: None,
                     }
                 ]
             },
@@ -134,22 +579,54 @@ async fn config_run() {
                     Channel {
                         address: "127.0.0.1:8010".to_string(),
                         protocol: Protocol::TCP,
-                        interest: r"^TCP 1$".to_string(),
+                        interest: r"^TCP 1$".into(),
+                        tls: None,
+                        ws_mode: None,
+                        duplex: None,
+                        propagate: false,
+                        idle_timeout_ms: None,
+                        compression: None,
+                        format: None,
+                        max_frame_length: None,
                     },
                     Channel {
                         address: "127.0.0.1:8011".to_string(),
                         protocol: Protocol::UDP,
-                        interest: r"^UDP 1$".to_string(),
+                        interest: r"^UDP 1$".into(),
+                        tls: None,
+                        ws_mode: None,
+                        duplex: None,
+                        propagate: false,
+                        idle_timeout_ms: None,
+                        compression: None,
+                        format: None,
+                        max_frame_length: None,
                     },
                     Channel {
                         address: "127.0.0.1:8020".to_string(),
                         protocol: Protocol::TCP,
-                        interest: r"^TCP 2$".to_string(),
+                        interest: r"^TCP 2$".into(),
+                        tls: None,
+                        ws_mode: None,
+                        duplex: None,
+                        propagate: false,
+                        idle_timeout_ms: None,
+                        compression: None,
+                        format: None,
+                        max_frame_length: None,
                     },
                     Channel {
                         address: "127.0.0.1:8021".to_string(),
                         protocol: Protocol::UDP,
-                        interest: r"^UDP 2$".to_string(),
+                        interest: r"^UDP 2$".into(),
+                        tls: None,
+                        ws_mode: None,
+                        duplex: None,
+                        propagate: false,
+                        idle_timeout_ms: None,
+                        compression: None,
+                        format: None,
+                        max_frame_length: None,
                     }
                 ]
             }),
@@ -162,8 +639,8 @@ async fn config_run() {
     let token = CancellationToken::new();
     let dispatcher = Dispatcher::new(32, token.clone());
 
-    let mut r_sub_tcp_rx = Subscription::subscribe(Interest::new(Regex::new(r"^TCP$").unwrap()), 32, dispatcher.clone()).await.unwrap();
-    let mut r_sub_udp_rx = Subscription::subscribe(Interest::new(Regex::new(r"^UDP$").unwrap()), 32, dispatcher.clone()).await.unwrap();
+    let (_, mut r_sub_tcp_rx) = Subscription::subscribe(Interest::new(Regex::new(r"^TCP$").unwrap()), 32, Backpressure::DropNewest, dispatcher.clone()).await.unwrap();
+    let (_, mut r_sub_udp_rx) = Subscription::subscribe(Interest::new(Regex::new(r"^UDP$").unwrap()), 32, Backpressure::DropNewest, dispatcher.clone()).await.unwrap();
 
     let (s1_tcp_tx, mut s1_tcp_rx) = mpsc::channel(32);
     let (s1_udp_tx, mut s1_udp_rx) = mpsc::channel(32);
@@ -171,18 +648,23 @@ async fn config_run() {
     let (s2_tcp_tx, mut s2_tcp_rx) = mpsc::channel(32);
     let (s2_udp_tx, mut s2_udp_rx) = mpsc::channel(32);
     
-    tcp::new_receiver("127.0.0.1:8010", s1_tcp_tx, token.clone()).await.unwrap();
-    udp::new_receiver("127.0.0.1:8011", s1_udp_tx, token.clone()).await.unwrap();
+    tcp::new_receiver("127.0.0.1:8010", Default::default(), s1_tcp_tx, None, token.clone()).await.unwrap();
+    udp::new_receiver("127.0.0.1:8011", s1_udp_tx, None, token.clone()).await.unwrap();
 
-    tcp::new_receiver("127.0.0.1:8020", s2_tcp_tx, token.clone()).await.unwrap();
-    udp::new_receiver("127.0.0.1:8021", s2_udp_tx, token.clone()).await.unwrap();
+    tcp::new_receiver("127.0.0.1:8020", Default::default(), s2_tcp_tx, None, token.clone()).await.unwrap();
+    udp::new_receiver("127.0.0.1:8021", s2_udp_tx, None, token.clone()).await.unwrap();
 
-    init_connections(path, false, dispatcher.clone(), 32, token.clone()).await.unwrap();
+    let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+    init_connections(path, false, dispatcher.clone(), 32, status_tx, Default::default(), token.clone()).await.unwrap();
+    for _ in 0..6 {
+        let status = status_rx.recv().await.unwrap();
+        assert!(matches!(status, Status::Listening(..) | Status::Connected(..)), "{:?}", status);
+    }
 
     let (r_send_tcp_tx, r_send_tcp_rx) = mpsc::channel(32);
     let (r_send_udp_tx, r_send_udp_rx) = mpsc::channel(32);
 
-    tcp::new_sender("127.0.0.1:8000", r_send_tcp_rx).await.unwrap();
+    tcp::new_sender("127.0.0.1:8000", Default::default(), r_send_tcp_rx).await.unwrap();
     udp::new_sender("127.0.0.1:8001", r_send_udp_rx).await.unwrap();
 
     let r_events = vec![
@@ -198,8 +680,8 @@ async fn config_run() {
     ];
 
     for event in r_events {
-        r_send_tcp_tx.send(event.clone()).await.unwrap();
-        r_send_udp_tx.send(event.clone()).await.unwrap();
+        r_send_tcp_tx.send(event.clone().into()).await.unwrap();
+        r_send_udp_tx.send(event.clone().into()).await.unwrap();
     }
 
     for event in s_events {
@@ -270,4 +752,802 @@ async fn config_run() {
     token.cancel();
 
     fs::remove_file(path).unwrap();
-}
\ No newline at end of file
+}
+#[test]
+fn pool() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            pool_run().await;
+        });
+}
+
+async fn pool_run() {
+    let config = Config {
+        receiver: None,
+        sender: Some(Node {
+            channels: vec![
+                Channel {
+                    address: "127.0.0.1:8030".to_string(),
+                    protocol: Protocol::TCP,
+                    interest: r"^A$".into(),
+                    tls: None,
+                    ws_mode: None,
+                    duplex: None,
+                    propagate: false,
+                    idle_timeout_ms: None,
+                    compression: None,
+                    format: None,
+                    max_frame_length: None,
+                },
+                Channel {
+                    address: "127.0.0.1:8030".to_string(),
+                    protocol: Protocol::TCP,
+                    interest: r"^B$".into(),
+                    tls: None,
+                    ws_mode: None,
+                    duplex: None,
+                    propagate: false,
+                    idle_timeout_ms: None,
+                    compression: None,
+                    format: None,
+                    max_frame_length: None,
+                },
+            ]
+        }),
+    };
+    let path = "./test-pool.toml";
+    std::fs::write(path, toml::to_string(&config).unwrap()).unwrap();
+
+    let token = CancellationToken::new();
+    let dispatcher = Dispatcher::new(32, token.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:8030").await.unwrap();
+
+    let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+    init_connections(path, false, dispatcher.clone(), 32, status_tx, Default::default(), token.clone()).await.unwrap();
+    let (stream, _) = listener.accept().await.unwrap();
+    let status = status_rx.recv().await.unwrap();
+    assert!(matches!(status, Status::Connected(..)), "{:?}", status);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for topic in ["A", "B", "C"] {
+        dispatcher.send(Command::Forward(Event::new(topic, Bytes::from_static("pooled".as_bytes())))).await.unwrap();
+    }
+    let mut framed = framing::frame_stream(stream);
+    assert_eq!(framed.next().await.unwrap().unwrap().topic, "A");
+    assert_eq!(framed.next().await.unwrap().unwrap().topic, "B");
+    assert!(tokio::time::timeout(Duration::from_millis(100), listener.accept()).await.is_err());
+    assert!(status_rx.try_recv().is_err());
+
+    token.cancel();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn custom_transport() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            custom_transport_run().await;
+        });
+}
+
+async fn custom_transport_run() {
+    let path = "./test-custom.toml";
+    std::fs::write(path, r#"
+        [receiver]
+        adv_topic = "adv"
+        adv_interest = "^adv$"
+
+        [[receiver.node.channels]]
+        address = "127.0.0.1:8040"
+        protocol = "MYTCP"
+        interest = "^custom in$"
+
+        [sender]
+        [[sender.channels]]
+        address = "127.0.0.1:8041"
+        protocol = "MYTCP"
+        interest = "^custom out$"
+        format = "json"
+
+        [[sender.channels]]
+        address = "127.0.0.1:8046"
+        protocol = "MISSING"
+        interest = "^custom$"
+
+        [[sender.channels]]
+        address = "127.0.0.1:8054"
+        protocol = "TCP"
+        interest = "^custom("
+    "#).unwrap();
+
+    let token = CancellationToken::new();
+    let dispatcher = Dispatcher::new(32, token.clone());
+    let mut registry = transport::Registry::new();
+    registry.register("MYTCP", tcp::TcpTransport);
+    let (peer_tx, mut peer_rx) = mpsc::channel(32);
+    let json = framing::FramingConfig { format: framing::Format::Json, ..Default::default() };
+    tcp::new_receiver("127.0.0.1:8041", json, peer_tx, None, token.clone()).await.unwrap();
+
+    let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+    init_connections(path, false, dispatcher.clone(), 32, status_tx, Arc::new(registry), token.clone()).await.unwrap();
+    let mut statuses = Vec::new();
+    for _ in 0..4 {
+        statuses.push(status_rx.recv().await.unwrap());
+    }
+    assert!(statuses.iter().any(|status| matches!(status, Status::Listening(Protocol::Custom(name), _) if name == "MYTCP")), "{:?}", statuses);
+    assert!(statuses.iter().any(|status| matches!(status, Status::Failed(Protocol::TCP, address, Error::Regex(_)) if address == "127.0.0.1:8054")), "{:?}", statuses);
+    assert!(statuses.iter().any(|status| matches!(status, Status::Connected(Protocol::Custom(name), _) if name == "MYTCP")), "{:?}", statuses);
+    assert!(statuses.iter().any(|status| matches!(status, Status::Failed(_, _, Error::UnknownProtocol(name)) if name == "MISSING")), "{:?}", statuses);
+
+    let (_, mut rx) = Subscription::subscribe(Interest::exact("custom in"), 32, Backpressure::DropNewest, dispatcher.clone()).await.unwrap();
+    let (peer_send_tx, peer_send_rx) = mpsc::channel(32);
+    tcp::new_sender("127.0.0.1:8040", Default::default(), peer_send_rx).await.unwrap();
+    peer_send_tx.send(Event::new("custom in", Bytes::from_static("registered".as_bytes())).into()).await.unwrap();
+    let received = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await.unwrap().unwrap();
+    assert_eq!(received.data, Bytes::from_static("registered".as_bytes()));
+    assert!(received.headers.contains_key(SOURCE_HEADER), "{:?}", received.headers);
+
+    dispatcher.send(Command::Forward(Event::new("custom out", Bytes::from_static("registered".as_bytes())))).await.unwrap();
+    let sent = tokio::time::timeout(Duration::from_secs(1), peer_rx.recv()).await.unwrap().unwrap();
+    assert_eq!(sent.topic, "custom out");
+
+    // The heartbeats keep the connection alive without being received.
+    let channel: Channel = toml::from_str(r#"
+        address = "127.0.0.1:8060"
+        protocol = "MYTCP"
+        interest = "^custom$"
+    "#).unwrap();
+    let (in_tx, mut in_rx) = mpsc::channel(32);
+    transport::new_receiver(Arc::new(tcp::TcpTransport), &channel, in_tx, token.clone()).await.unwrap();
+    let (peer_send_tx, peer_send_rx) = mpsc::channel(32);
+    tcp::new_sender("127.0.0.1:8060", Default::default(), peer_send_rx).await.unwrap();
+    peer_send_tx.send(heartbeat::heartbeat_event().into()).await.unwrap();
+    peer_send_tx.send(Event::new("custom", Bytes::new()).into()).await.unwrap();
+    let received = tokio::time::timeout(Duration::from_secs(1), in_rx.recv()).await.unwrap().unwrap();
+    assert_eq!(received.topic, "custom");
+
+    token.cancel();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn redirect_failures() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            redirect_failures_run().await;
+        });
+}
+
+async fn redirect_failures_run() {
+    let path = "./test-redirect.toml";
+    std::fs::write(path, r#"
+        [receiver]
+        adv_topic = "adv"
+        adv_interest = "^adv$"
+
+        [[receiver.node.channels]]
+        address = "127.0.0.1:8055"
+        protocol = "TCP"
+        interest = "^redirect$"
+    "#).unwrap();
+
+    let token = CancellationToken::new();
+    let dispatcher = Dispatcher::new(32, token.clone());
+    let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+    init_connections(path, true, dispatcher.clone(), 32, status_tx, Default::default(), token.clone()).await.unwrap();
+    assert!(matches!(status_rx.recv().await.unwrap(), Status::Listening(Protocol::TCP, _)));
+
+    let (tx, rx) = mpsc::channel(32);
+    tcp::new_sender("127.0.0.1:8055", Default::default(), rx).await.unwrap();
+    tx.send(Event::new("adv", Bytes::from_static(&[0xff, 0xfe])).into()).await.unwrap();
+    tx.send(Event::new("adv", Bytes::from_static("adv_topic = [".as_bytes())).into()).await.unwrap();
+    let invalid = r#"
+        adv_topic = "adv"
+        adv_interest = "^adv$"
+
+        [[node.channels]]
+        address = "127.0.0.1:8056"
+        protocol = "TCP"
+        interest = "^redirect("
+    "#;
+    tx.send(Event::new("adv", Bytes::from(invalid)).into()).await.unwrap();
+
+    let status = tokio::time::timeout(Duration::from_secs(1), status_rx.recv()).await.unwrap().unwrap();
+    assert!(matches!(&status, Status::Failed(Protocol::TCP, address, Error::Codec(_)) if address == "127.0.0.1:8055"), "{:?}", status);
+    let status = tokio::time::timeout(Duration::from_secs(1), status_rx.recv()).await.unwrap().unwrap();
+    assert!(matches!(&status, Status::Failed(Protocol::TCP, address, Error::Config(_)) if address == "127.0.0.1:8055"), "{:?}", status);
+    let status = tokio::time::timeout(Duration::from_secs(1), status_rx.recv()).await.unwrap().unwrap();
+    assert!(matches!(&status, Status::Failed(Protocol::TCP, address, Error::Regex(_)) if address == "127.0.0.1:8056"), "{:?}", status);
+
+    token.cancel();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn conflicting_channels() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            conflicting_channels_run().await;
+        });
+}
+
+async fn conflicting_channels_run() {
+    let path = "./test-conflicting.toml";
+    std::fs::write(path, r#"
+        [sender]
+        [[sender.channels]]
+        address = "127.0.0.1:8057"
+        protocol = "UDP"
+        interest = "^conflicting$"
+
+        [[sender.channels]]
+        address = "127.0.0.1:8057"
+        protocol = "UDP"
+        interest = "^conflicting$"
+        format = "json"
+    "#).unwrap();
+
+    let token = CancellationToken::new();
+    let dispatcher = Dispatcher::new(32, token.clone());
+    let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+    init_connections(path, false, dispatcher.clone(), 32, status_tx, Arc::new(transport::Registry::new()), token.clone()).await.unwrap();
+    let mut statuses = Vec::new();
+    for _ in 0..2 {
+        statuses.push(tokio::time::timeout(Duration::from_secs(1), status_rx.recv()).await.unwrap().unwrap());
+    }
+    assert!(statuses.iter().any(|status| matches!(status, Status::Connected(Protocol::UDP, _))), "{:?}", statuses);
+    assert!(statuses.iter().any(|status| matches!(status, Status::Failed(Protocol::UDP, address, Error::ConflictingChannel) if address == "127.0.0.1:8057")), "{:?}", statuses);
+
+    token.cancel();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn shared_failure() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            shared_failure_run().await;
+        });
+}
+
+async fn shared_failure_run() {
+    let path = "./test-shared-failure.toml";
+    std::fs::write(path, r#"
+        [sender]
+        [[sender.channels]]
+        address = "127.0.0.1:8058"
+        protocol = "TCP"
+        interest = "^shared$"
+        duplex = "^back$"
+
+        [[sender.channels]]
+        address = "127.0.0.1:8058"
+        protocol = "TCP"
+        interest = "^shared two$"
+        duplex = "^back$"
+    "#).unwrap();
+
+    let token = CancellationToken::new();
+    let dispatcher = Dispatcher::new(32, token.clone());
+    let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+    init_connections(path, false, dispatcher.clone(), 32, status_tx, Arc::new(transport::Registry::new()), token.clone()).await.unwrap();
+    for _ in 0..2 {
+        let status = tokio::time::timeout(Duration::from_secs(1), status_rx.recv()).await.unwrap().unwrap();
+        assert!(matches!(&status, Status::Failed(Protocol::TCP, address, Error::Connect(_)) if address == "127.0.0.1:8058"), "{:?}", status);
+    }
+
+    token.cancel();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn duplex() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            duplex_run().await;
+        });
+}
+
+async fn duplex_run() {
+    let (listener_path, sender_path) = ("./test-duplex-listener.toml", "./test-duplex-sender.toml");
+    std::fs::write(listener_path, r#"
+        [receiver]
+        adv_topic = "adv"
+        adv_interest = "^adv$"
+
+        [[receiver.node.channels]]
+        address = "127.0.0.1:8042"
+        protocol = "TCP"
+        interest = "^up$"
+        duplex = "^down"
+    "#).unwrap();
+    std::fs::write(sender_path, r#"
+        [[sender.channels]]
+        address = "127.0.0.1:8042"
+        protocol = "TCP"
+        interest = "^up$"
+        duplex = { any = ["^down$", "^other$"] }
+    "#).unwrap();
+
+    let token = CancellationToken::new();
+    let (listener, sender) = (Dispatcher::new(32, token.clone()), Dispatcher::new(32, token.clone()));
+    let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+    init_connections(listener_path, false, listener.clone(), 32, status_tx.clone(), Default::default(), token.clone()).await.unwrap();
+    assert!(matches!(status_rx.recv().await.unwrap(), Status::Listening(..)));
+    init_connections(sender_path, false, sender.clone(), 32, status_tx, Default::default(), token.clone()).await.unwrap();
+    assert!(matches!(status_rx.recv().await.unwrap(), Status::Connected(..)));
+
+    let (_, mut up_rx) = Subscription::subscribe(Interest::new(Regex::new(r"^up$").unwrap()), 32, Backpressure::DropNewest, listener.clone()).await.unwrap();
+    let (_, mut down_rx) = Subscription::subscribe(Interest::new(Regex::new(r"^(down|downstream|other)$").unwrap()), 32, Backpressure::DropNewest, sender.clone()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    sender.send(Command::Forward(Event::new("up", Bytes::from_static("request".as_bytes())))).await.unwrap();
+    let up = up_rx.recv().await.unwrap();
+    assert_eq!(up.data, Bytes::from_static("request".as_bytes()));
+
+    for topic in ["downstream", "other", "down"] {
+        listener.send(Command::Forward(Event::new(topic, Bytes::from_static("reply".as_bytes())))).await.unwrap();
+    }
+    let down = tokio::time::timeout(Duration::from_secs(1), down_rx.recv()).await.unwrap().unwrap();
+    assert_eq!(down.topic, "down");
+    assert!(down.headers.contains_key(SOURCE_HEADER), "{:?}", down.headers);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(down_rx.try_recv().is_err());
+
+    token.cancel();
+
+    fs::remove_file(listener_path).unwrap();
+    fs::remove_file(sender_path).unwrap();
+}
+
+#[test]
+fn propagation() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            propagation_run().await;
+        });
+}
+
+async fn propagation_run() {
+    let (listener_path, sender_path) = ("./test-propagation-listener.toml", "./test-propagation-sender.toml");
+    std::fs::write(listener_path, r#"
+        [receiver]
+        adv_topic = "adv"
+        adv_interest = "^adv$"
+
+        [[receiver.node.channels]]
+        address = "127.0.0.1:8043"
+        protocol = "TCP"
+        interest = "^(a|b)$"
+        propagate = true
+    "#).unwrap();
+    std::fs::write(sender_path, r#"
+        [[sender.channels]]
+        address = "127.0.0.1:8043"
+        protocol = "TCP"
+        interest = "^(a|b)$"
+        propagate = true
+    "#).unwrap();
+
+    let token = CancellationToken::new();
+    let (listener, sender) = (Dispatcher::new(32, token.clone()), Dispatcher::new(32, token.clone()));
+    let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+    init_connections(listener_path, false, listener.clone(), 32, status_tx.clone(), Default::default(), token.clone()).await.unwrap();
+    assert!(matches!(status_rx.recv().await.unwrap(), Status::Listening(..)));
+    init_connections(sender_path, false, sender.clone(), 32, status_tx, Default::default(), token.clone()).await.unwrap();
+    assert!(matches!(status_rx.recv().await.unwrap(), Status::Connected(..)));
+
+    // Observers not advertised, to check what crosses the link.
+    let (observer, mut observed) = Subscription::new(Interest::new(Regex::new(r"^(a|b)$").unwrap()), 32, Backpressure::DropNewest);
+    listener.send(Command::Subscribe(observer.advertised(false))).await.unwrap();
+    let (handle, mut a_rx) = Subscription::subscribe(Interest::exact("a"), 32, Backpressure::DropNewest, listener.clone()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for topic in ["b", "a"] {
+        sender.send(Command::Forward(Event::new(topic, Bytes::from_static("propagated".as_bytes())))).await.unwrap();
+    }
+    assert_eq!(a_rx.recv().await.unwrap().topic, "a");
+    assert_eq!(observed.recv().await.unwrap().topic, "a");
+
+    handle.cancel().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    sender.send(Command::Forward(Event::new("a", Bytes::from_static("withdrawn".as_bytes())))).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(observed.try_recv().is_err());
+
+    let (_, mut b_rx) = Subscription::subscribe(Interest::exact("b"), 32, Backpressure::DropNewest, sender.clone()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    listener.send(Command::Forward(Event::new("b", Bytes::from_static("reverse".as_bytes())))).await.unwrap();
+    let reverse = tokio::time::timeout(Duration::from_secs(1), b_rx.recv()).await.unwrap().unwrap();
+    assert_eq!(reverse.data, Bytes::from_static("reverse".as_bytes()));
+    assert!(!observed.recv().await.unwrap().headers.contains_key(SOURCE_HEADER));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(observed.try_recv().is_err());
+
+    token.cancel();
+
+    fs::remove_file(listener_path).unwrap();
+    fs::remove_file(sender_path).unwrap();
+}
+
+#[test]
+fn loop_prevention() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            loop_prevention_run().await;
+        });
+}
+
+async fn loop_prevention_run() {
+    let (a_path, b_path) = ("./test-loop-a.toml", "./test-loop-b.toml");
+    for (path, receiver, sender) in [(a_path, 8044, 8045), (b_path, 8045, 8044)] {
+        std::fs::write(path, format!(r#"
+            [receiver]
+            adv_topic = "adv"
+            adv_interest = "^adv$"
+
+            [[receiver.node.channels]]
+            address = "127.0.0.1:{}"
+            protocol = "TCP"
+            interest = "^x$"
+
+            [[sender.channels]]
+            address = "127.0.0.1:{}"
+            protocol = "TCP"
+            interest = "^x$"
+        "#, receiver, sender)).unwrap();
+    }
+
+    let token = CancellationToken::new();
+    let (a, b) = (Dispatcher::new(32, token.clone()), Dispatcher::new(32, token.clone()));
+    let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+    init_connections(a_path, false, a.clone(), 32, status_tx.clone(), Default::default(), token.clone()).await.unwrap();
+    init_connections(b_path, false, b.clone(), 32, status_tx, Default::default(), token.clone()).await.unwrap();
+    let mut connected = 0;
+    while connected < 2 {
+        match status_rx.recv().await.unwrap() {
+            Status::Connected(..) => connected += 1,
+            Status::Listening(..) | Status::Disconnected(..) => {},
+            status => panic!("{:?}", status),
+        }
+    }
+
+    let (_, mut a_rx) = Subscription::subscribe(Interest::exact("x"), 32, Backpressure::DropNewest, a.clone()).await.unwrap();
+    let (_, mut b_rx) = Subscription::subscribe(Interest::exact("x"), 32, Backpressure::DropNewest, b.clone()).await.unwrap();
+    a.send(Command::Forward(Event::new("x", Bytes::from_static("ping".as_bytes())))).await.unwrap();
+    let local = a_rx.recv().await.unwrap();
+    let remote = tokio::time::timeout(Duration::from_secs(1), b_rx.recv()).await.unwrap().unwrap();
+    assert_eq!((remote.id, remote.origin, remote.hops), (local.id, local.origin, 1));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(a_rx.try_recv().is_err());
+    assert!(b_rx.try_recv().is_err());
+
+    let (_, mut y_rx) = Subscription::subscribe(Interest::exact("y"), 32, Backpressure::DropNewest, a.clone()).await.unwrap();
+    let relayed = Event { origin: Some(7), hops: 1, ..Event::new("y", Bytes::from_static("relayed".as_bytes())) };
+    for event in [relayed.clone(), relayed, Event { origin: Some(7), hops: 3, ..Event::new("y", Bytes::new()).with_ttl(2) }] {
+        a.send(Command::Forward(event)).await.unwrap();
+    }
+    assert_eq!(y_rx.recv().await.unwrap().data, Bytes::from_static("relayed".as_bytes()));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(y_rx.try_recv().is_err());
+
+    token.cancel();
+
+    fs::remove_file(a_path).unwrap();
+    fs::remove_file(b_path).unwrap();
+}
+
+#[test]
+fn heartbeat() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            heartbeat_run().await;
+        });
+}
+
+async fn heartbeat_run() {
+    let token = CancellationToken::new();
+    let (tx, mut rx) = mpsc::channel(32);
+    tcp::new_receiver("127.0.0.1:8047", Default::default(), tx.clone(), Some(Duration::from_millis(300)), token.clone()).await.unwrap();
+    let silent = tokio::net::TcpStream::connect("127.0.0.1:8047").await.unwrap();
+    let lost = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await.unwrap().unwrap();
+    assert_eq!(lost.topic, heartbeat::CONNECTION_LOST_TOPIC);
+    assert_eq!(lost.data, Bytes::from(silent.local_addr().unwrap().to_string()));
+    assert!(heartbeat::is_system(&lost));
+
+    udp::new_receiver("127.0.0.1:8048", tx.clone(), Some(Duration::from_millis(300)), token.clone()).await.unwrap();
+    let (s_tx, s_rx) = mpsc::channel(32);
+    udp::new_sender("127.0.0.1:8048", s_rx).await.unwrap();
+    s_tx.send(Event::new("last", Bytes::new()).into()).await.unwrap();
+    drop(s_tx);
+    assert_eq!(rx.recv().await.unwrap().topic, "last");
+    let lost = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await.unwrap().unwrap();
+    assert_eq!(lost.topic, heartbeat::CONNECTION_LOST_TOPIC);
+
+    tcp::new_receiver("127.0.0.1:8049", Default::default(), tx, Some(Duration::from_millis(1500)), token.clone()).await.unwrap();
+    let (s_tx, s_rx) = mpsc::channel(32);
+    tcp::new_sender("127.0.0.1:8049", Default::default(), s_rx).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert!(rx.try_recv().is_err());
+    s_tx.send(Event::new("alive", Bytes::new()).into()).await.unwrap();
+    assert_eq!(rx.recv().await.unwrap().topic, "alive");
+
+    token.cancel();
+}
+
+#[test]
+fn compression() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            compression_run().await;
+        });
+}
+
+async fn compression_run() {
+    let model = Bytes::from("weights = [0, 1, 2, 3, 4, 5, 6, 7]\n".repeat(4096));
+    for algorithm in [framing::Algorithm::Zstd, framing::Algorithm::Lz4] {
+        let frame = framing::encode(&Event::new("model", model.clone())).unwrap();
+        let compressed = framing::compress(&frame, algorithm).unwrap();
+        assert!(compressed.len() < frame.len() / 10, "{:?}", algorithm);
+        assert_eq!(framing::decode(&compressed).unwrap().data, model);
+    }
+
+    // A streamed zstd frame has no content size, so its expansion is bounded while decoding.
+    let frame = framing::encode(&Event::new("model", model.clone())).unwrap();
+    let mut streamed = framing::compress(&[], framing::Algorithm::Zstd).unwrap()[..framing::MAGIC.len() + 2].to_vec();
+    streamed.extend(zstd::stream::encode_all(&frame[..], 0).unwrap());
+    assert!(matches!(zstd::zstd_safe::get_frame_content_size(&streamed[framing::MAGIC.len() + 2..]), Ok(None)));
+    assert_eq!(framing::decode(&streamed).unwrap().data, model);
+    let mut codec = framing::EventCodec::new(framing::FramingConfig { max_frame_length: 1024, ..Default::default() });
+    let e = tokio_serde::Deserializer::<Event>::deserialize(std::pin::Pin::new(&mut codec), &streamed[..].into()).unwrap_err();
+    assert!(framing::is_too_large(&e), "{:?}", e);
+
+    let token = CancellationToken::new();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:8050").await.unwrap();
+    let (tx, rx) = mpsc::channel(32);
+    let compression = framing::Compression { algorithm: framing::Algorithm::Zstd, threshold: 1024 };
+    let framing = framing::FramingConfig { compression: Some(compression), ..Default::default() };
+    let _state = tcp::new_reconnecting_sender("127.0.0.1:8050", framing, rx, tcp::Backoff::default(), 32, token.clone());
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = framing::frame_string(stream);
+
+    let offer = framing::decode(&framed.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(offer.topic, framing::COMPRESSION_OFFER_TOPIC);
+    tx.send(Event::new("model", model.clone()).into()).await.unwrap();
+    let plain = framed.next().await.unwrap().unwrap();
+    assert_eq!(framing::wire_version(&plain), framing::WIRE_VERSION);
+
+    let accept = framing::accept_compression(&offer).unwrap();
+    futures::SinkExt::send(&mut framed, framing::encode(&accept).unwrap()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    tx.send(Event::new("small", Bytes::from_static("small".as_bytes())).into()).await.unwrap();
+    tx.send(Event::new("model", model.clone()).into()).await.unwrap();
+    let small = framed.next().await.unwrap().unwrap();
+    assert_eq!(framing::wire_version(&small), framing::WIRE_VERSION);
+    let compressed = framed.next().await.unwrap().unwrap();
+    assert_eq!(framing::wire_version(&compressed), framing::COMPRESSED_FRAME);
+    assert!(compressed.len() < plain.len() / 10);
+    assert_eq!(framing::decode(&compressed).unwrap().data, model);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:8059").await.unwrap();
+    let (tx, rx) = mpsc::channel(32);
+    tcp::new_sender("127.0.0.1:8059", framing, rx).await.unwrap();
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = framing::frame_string(stream);
+    let offer = framing::decode(&framed.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(offer.topic, framing::COMPRESSION_OFFER_TOPIC);
+    futures::SinkExt::send(&mut framed, framing::encode(&framing::accept_compression(&offer).unwrap()).unwrap()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    tx.send(Event::new("model", model.clone()).into()).await.unwrap();
+    let compressed = framed.next().await.unwrap().unwrap();
+    assert_eq!(framing::wire_version(&compressed), framing::COMPRESSED_FRAME);
+    assert_eq!(framing::decode(&compressed).unwrap().data, model);
+
+    token.cancel();
+}
+
+#[test]
+fn formats() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            formats_run().await;
+        });
+}
+
+async fn formats_run() {
+    let event = Event::new("format", Bytes::from_static(&[0, 1, 2, 255]))
+        .with_header("text", "value")
+        .with_header("raw", Bytes::from_static(&[3, 4]))
+        .with_ttl(5);
+    for format in [framing::Format::Bincode, framing::Format::MessagePack, framing::Format::Cbor, framing::Format::Json, framing::Format::Protobuf] {
+        let frame = framing::encode_with(&event, format).unwrap();
+        let decoded = framing::decode_with(&frame, format).unwrap();
+        assert_eq!((&decoded.topic, &decoded.data, &decoded.headers), (&event.topic, &event.data, &event.headers), "{:?}", format);
+        assert_eq!((decoded.timestamp.timestamp_micros(), decoded.id, decoded.hops, decoded.ttl), (event.timestamp.timestamp_micros(), event.id, 1, 5), "{:?}", format);
+        let compressed = framing::compress(&frame, framing::Algorithm::Lz4).unwrap();
+        assert_eq!(framing::decode_with(&compressed, format).unwrap().data, event.data, "{:?}", format);
+    }
+
+    let token = CancellationToken::new();
+    let (tx, mut rx) = mpsc::channel(32);
+    let json = framing::FramingConfig { format: framing::Format::Json, ..Default::default() };
+    tcp::new_receiver("127.0.0.1:8051", json, tx, None, token.clone()).await.unwrap();
+    let stream = tokio::net::TcpStream::connect("127.0.0.1:8051").await.unwrap();
+    let mut framed = framing::frame_string(stream);
+    let frame = r#"{"topic":"json","timestamp":"2024-01-01T00:00:00Z","data":[104,105],"correlation_id":null,"headers":{"lang":{"Text":"python"}}}"#;
+    futures::SinkExt::send(&mut framed, Bytes::from(frame)).await.unwrap();
+    let received = rx.recv().await.unwrap();
+    assert_eq!((received.topic.as_str(), &received.data[..], received.ttl), ("json", "hi".as_bytes(), DEFAULT_TTL));
+    assert_eq!(received.header("lang").and_then(HeaderValue::as_str), Some("python"));
+    let minimal = framing::decode_with(br#"{"topic":"json","timestamp":"2024-01-01T00:00:00Z","data":[]}"#, framing::Format::Json).unwrap();
+    assert_eq!((minimal.correlation_id, minimal.headers.len()), (None, 0));
+
+    token.cancel();
+}
+
+#[test]
+fn frame_limit() {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            frame_limit_run().await;
+        });
+}
+
+async fn frame_limit_run() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let token = CancellationToken::new();
+    let (tx, mut rx) = mpsc::channel(32);
+    let framing = framing::FramingConfig { max_frame_length: 1024, ..Default::default() };
+    let rejected = framing::oversize_frames();
+    tcp::new_receiver("127.0.0.1:8052", framing, tx, None, token.clone()).await.unwrap();
+    let mut oversize = tokio::net::TcpStream::connect("127.0.0.1:8052").await.unwrap();
+    oversize.write_all(&4096u32.to_le_bytes()).await.unwrap();
+    let read = tokio::time::timeout(Duration::from_secs(1), oversize.read(&mut [0; 16])).await.unwrap().unwrap();
+    assert_eq!(read, 0);
+    assert!(framing::oversize_frames() > rejected);
+
+    let stream = tokio::net::TcpStream::connect("127.0.0.1:8052").await.unwrap();
+    let mut framed = framing::frame_string(stream);
+    futures::SinkExt::send(&mut framed, framing::encode(&Event::new("fits", Bytes::new())).unwrap()).await.unwrap();
+    assert_eq!(rx.recv().await.unwrap().topic, "fits");
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:8053").await.unwrap();
+    let (s_tx, s_rx) = mpsc::channel(32);
+    let rejected = framing::oversize_frames();
+    let _state = tcp::new_reconnecting_sender("127.0.0.1:8053", framing, s_rx, tcp::Backoff::default(), 32, token.clone());
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = framing::frame_string(stream);
+    s_tx.send(Event::new("large", Bytes::from(vec![0; 2048])).into()).await.unwrap();
+    s_tx.send(Event::new("small", Bytes::new()).into()).await.unwrap();
+    assert_eq!(framing::decode(&framed.next().await.unwrap().unwrap()).unwrap().topic, "small");
+    assert!(framing::oversize_frames() > rejected);
+
+    token.cancel();
+}
+
+#[test]
+fn wire() {
+    let event = Event::new("wire", Bytes::from_static("success".as_bytes()))
+        .with_header("reply", "wire reply")
+        .with_header("raw", Bytes::from_static(&[0, 1, 2]));
+
+    let mut codec = framing::EventCodec::default();
+    let frame = tokio_serde::Serializer::serialize(std::pin::Pin::new(&mut codec), &event).unwrap();
+    assert_eq!(framing::wire_version(&frame), framing::WIRE_VERSION);
+    let decoded = tokio_serde::Deserializer::<Event>::deserialize(std::pin::Pin::new(&mut codec), &frame[..].into()).unwrap();
+    assert_eq!(decoded.headers, event.headers);
+    assert!(Interest::Header("reply".to_string(), Regex::new(r"^wire").unwrap()).is_valid(&decoded));
+    assert!(!Interest::Header("missing".to_string(), Regex::new(r"").unwrap()).is_valid(&decoded));
+    assert_eq!((decoded.id, decoded.origin, decoded.hops, decoded.ttl), (event.id, event.origin, event.hops + 1, event.ttl));
+
+    let mut v2 = framing::MAGIC.to_vec();
+    v2.push(framing::V2_VERSION);
+    v2.extend(bincode::serialize(&(&event.topic, &event.timestamp, &event.data, &event.correlation_id, &event.headers)).unwrap());
+    let decoded = tokio_serde::Deserializer::<Event>::deserialize(std::pin::Pin::new(&mut codec), &v2[..].into()).unwrap();
+    assert_eq!((&decoded.topic, decoded.timestamp, &decoded.headers), (&event.topic, event.timestamp, &event.headers));
+    assert_eq!((decoded.origin, decoded.hops, decoded.ttl), (None, 1, DEFAULT_TTL));
+
+    let legacy = bincode::serialize(&(&event.topic, &event.timestamp, &event.data)).unwrap();
+    assert_eq!(framing::wire_version(&legacy), framing::LEGACY_VERSION);
+    let decoded = tokio_serde::Deserializer::<Event>::deserialize(std::pin::Pin::new(&mut codec), &legacy[..].into()).unwrap();
+    assert_eq!((decoded.topic, decoded.timestamp, decoded.data), (event.topic, event.timestamp, event.data));
+    assert!(decoded.headers.is_empty());
+}
+
+#[test]
+fn fan_out() {
+    let model = Arc::new(Event::new("model", Bytes::from(vec![7; 2 * 1024 * 1024])));
+    let frames: Vec<Bytes> = (0..3).map(|_| {
+        let mut codec = framing::EventCodec::default();
+        tokio_serde::Serializer::<Arc<Event>>::serialize(std::pin::Pin::new(&mut codec), &model).unwrap()
+    }).collect();
+    assert!(frames.iter().all(|frame| frame.as_ptr() == frames[0].as_ptr()));
+    assert_eq!(framing::encode_shared(&model).unwrap().as_ptr(), frames[0].as_ptr());
+    assert_eq!(framing::decode(&frames[0]).unwrap().data, model.data);
+
+    let mut json = framing::EventCodec::new(framing::FramingConfig { format: framing::Format::Json, ..Default::default() });
+    let frame = tokio_serde::Serializer::<Arc<Event>>::serialize(std::pin::Pin::new(&mut json), &model).unwrap();
+    assert_ne!(frame.as_ptr(), frames[0].as_ptr());
+    assert_eq!(framing::decode_with(&frame, framing::Format::Json).unwrap().data, model.data);
+
+    let mut copy = model.as_ref().clone();
+    copy.topic = String::from("copy");
+    assert_eq!(framing::decode(&framing::encode_shared(&Arc::new(copy)).unwrap()).unwrap().topic, "copy");
+}
+
+#[test]
+fn regex_set_limit() {
+    // Each regex fits the size limit, but not all of them in a `RegexSet`.
+    let mut index = index::TopicIndex::new();
+    let patterns: Vec<String> = (0..4).map(|i| format!(r"^{}\w{{70}}$", i)).collect();
+    for pattern in &patterns {
+        assert!(Regex::new(pattern).is_ok());
+    }
+    assert!(regex::RegexSet::new(&patterns).is_err());
+    for (i, pattern) in patterns.iter().enumerate() {
+        index.insert(SubscriptionId(i as u64), &Interest::new(Regex::new(pattern).unwrap()));
+    }
+    assert_eq!(index.matches(&format!("3{}", "a".repeat(70))), vec![SubscriptionId(3)]);
+}
+
+#[test]
+fn interest_expr() {
+    let channel: Channel = toml::from_str(r#"
+        address = "127.0.0.1:8000"
+        protocol = "TCP"
+        interest = { all = [
+            { wildcard = "model/+" },
+            { any = [{ size = { max = 4 } }, { header = { name = "round", matches = "^[0-9]+$" } }] },
+            { not = { source = "^10\\." } },
+        ] }
+    "#).unwrap();
+    let interest = channel.interest.build().unwrap();
+
+    assert!(interest.is_valid(&Event::new("model/alice", Bytes::from_static("tiny".as_bytes()))));
+    assert!(interest.is_valid(&Event::new("model/alice", Bytes::from_static("weights".as_bytes())).with_header("round", "3")));
+    assert!(!interest.is_valid(&Event::new("model/alice", Bytes::from_static("weights".as_bytes()))));
+    assert!(!interest.is_valid(&Event::new("model/alice/old", Bytes::new())));
+    assert!(!interest.is_valid(&Event::new("model/alice", Bytes::new()).with_header(SOURCE_HEADER, "10.0.0.1:8000")));
+
+    let spec: InterestSpec = toml::from_str::<Channel>(&toml::to_string(&channel).unwrap()).unwrap().interest;
+    assert!(matches!(spec, InterestSpec::Expr(InterestExpr::All(specs)) if specs.len() == 3));
+}